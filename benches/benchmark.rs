@@ -1,4 +1,7 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use kopek::fft::{self, FftPlan};
+use kopek::oscillator::{AntiAliasing, Oscillator, WaveType};
+use num::complex::Complex;
 
 fn real_sine(phase: f32) -> f32 {
     phase.sin()
@@ -28,5 +31,102 @@ fn bench_sine_functions(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, bench_sine_functions);
+fn bench_fft(c: &mut Criterion) {
+    const SIZE: usize = 1024;
+    let input: Vec<Complex<f64>> = (0..SIZE)
+        .map(|i| Complex::new((i as f64 * 0.1).sin(), 0.0))
+        .collect();
+
+    c.bench_function("fft 1024", |b| {
+        b.iter(|| black_box(fft::fft(black_box(&input))))
+    });
+
+    let plan = FftPlan::new(SIZE);
+    let mut buffer = input.clone();
+    c.bench_function("fft plan 1024", |b| {
+        b.iter(|| {
+            buffer.copy_from_slice(&input);
+            plan.forward(black_box(&mut buffer));
+        })
+    });
+
+    // Not a power of two, transformed with the mixed radix path
+    let plan = FftPlan::new(1000);
+    let mut buffer = input[..1000].to_vec();
+    let mut scratch = vec![Complex::new(0.0, 0.0); plan.scratch_len()];
+    c.bench_function("fft plan 1000", |b| {
+        b.iter(|| {
+            buffer.copy_from_slice(&input[..1000]);
+            plan.forward_with_scratch(black_box(&mut buffer), &mut scratch);
+        })
+    });
+
+    // The f32 path used for real-time spectrum display
+    let plan = FftPlan::<f32>::new(SIZE);
+    let input_f32: Vec<Complex<f32>> = input
+        .iter()
+        .map(|c| Complex::new(c.re as f32, c.im as f32))
+        .collect();
+    let mut buffer_f32 = input_f32.clone();
+    c.bench_function("fft plan f32 1024", |b| {
+        b.iter(|| {
+            buffer_f32.copy_from_slice(&input_f32);
+            plan.forward(black_box(&mut buffer_f32));
+        })
+    });
+
+    let real: Vec<f32> = input.iter().map(|c| c.re as f32).collect();
+    c.bench_function("rfft 1024", |b| {
+        b.iter(|| black_box(fft::rfft(black_box(&real))))
+    });
+
+    let plan = fft::RfftPlan::<f32>::new(SIZE);
+    let mut spectrum = vec![Complex::new(0.0, 0.0); plan.spectrum_len()];
+    c.bench_function("rfft plan 1024", |b| {
+        b.iter(|| plan.forward(black_box(&real), &mut spectrum))
+    });
+}
+
+fn bench_oscillator(c: &mut Criterion) {
+    const SIZE: usize = 1024;
+    let mut output = vec![0.0; SIZE];
+    let frequencies: Vec<f32> = (0..SIZE).map(|i| 440.0 + i as f32 * 0.1).collect();
+
+    for (name, wave_type, anti_aliasing) in [
+        ("sine", WaveType::Sine, AntiAliasing::None),
+        ("sawtooth", WaveType::Sawtooth, AntiAliasing::PolyBlep),
+    ] {
+        let mut oscillator = Oscillator::new(44100.0);
+        oscillator.set_frequency(440.0);
+        oscillator.set_wave_type(wave_type);
+        oscillator.set_anti_aliasing(anti_aliasing);
+
+        // The per-sample path every example used to take
+        c.bench_function(&format!("oscillator run {} {}", name, SIZE), |b| {
+            b.iter(|| {
+                for value in output.iter_mut() {
+                    *value = oscillator.run();
+                }
+                black_box(&output);
+            })
+        });
+
+        c.bench_function(&format!("oscillator process {} {}", name, SIZE), |b| {
+            b.iter(|| {
+                oscillator.process(black_box(&mut output), None);
+            })
+        });
+
+        c.bench_function(
+            &format!("oscillator process frequencies {} {}", name, SIZE),
+            |b| {
+                b.iter(|| {
+                    oscillator.process(black_box(&mut output), Some(&frequencies));
+                })
+            },
+        );
+    }
+}
+
+criterion_group!(benches, bench_sine_functions, bench_fft, bench_oscillator);
 criterion_main!(benches);
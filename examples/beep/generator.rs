@@ -36,11 +36,13 @@ impl Generator {
         view_producer: HeapProducer<f32>,
         sample_rate: f32,
     ) -> Result<Generator, anyhow::Error> {
+        let mut oscillator = Oscillator::new(sample_rate);
+        oscillator.set_anti_aliasing(AntiAliasing::PolyBlep);
         Ok(Generator {
             is_running: false,
             tick: 0,
             freq: C_FREQ,
-            oscillator: Oscillator::new(sample_rate),
+            oscillator,
             oscillator_type: OscillatorType::Sine,
             noise: Noise::new(),
             noise_type: NoiseType::None,
@@ -51,32 +53,32 @@ impl Generator {
     }
 
     pub fn update(&mut self) {
-        for _ in 0..1024 {
-            if !self.producer.is_full() {
-                let mut value = 0.0;
-                if self.is_running {
-                    self.oscillator.set_frequency(self.freq);
-                    value = match self.oscillator_type {
-                        OscillatorType::Sine => self.oscillator.sine(),
-                        OscillatorType::Sawtooth => self.oscillator.sawtooth(),
-                        OscillatorType::Square => self.oscillator.square(0.5),
-                        OscillatorType::Triangle => self.oscillator.triangle(),
-                        OscillatorType::FakeSine => self.oscillator.fake_sine(),
-                    };
-                    value += match self.noise_type {
-                        NoiseType::None => 0.0,
-                        NoiseType::Random => self.noise.rand_noise(),
-                        NoiseType::White => self.noise.white_noise(),
-                    };
-                    // let value = kopek::wave::white_noise();
-                    // let value = kopek::wave::rand_noise();
-                }
-                self.producer.push(value).unwrap();
-                if !self.view_producer.is_full() {
-                    self.view_producer.push(value).unwrap();
-                }
-                self.tick += 1;
+        let mut block = [0.0; 1024];
+        let block = &mut block[..self.producer.free_len().min(1024)];
+        if self.is_running {
+            self.oscillator.set_frequency(self.freq);
+            self.oscillator.set_wave_type(match self.oscillator_type {
+                OscillatorType::Sine => WaveType::Sine,
+                OscillatorType::Sawtooth => WaveType::Sawtooth,
+                OscillatorType::Square => WaveType::Square { duty: 0.5 },
+                OscillatorType::Triangle => WaveType::Triangle,
+                OscillatorType::FakeSine => WaveType::FakeSine,
+            });
+            self.oscillator.process(block, None);
+        }
+        for value in block.iter_mut() {
+            if self.is_running {
+                *value += match self.noise_type {
+                    NoiseType::None => 0.0,
+                    NoiseType::Random => self.noise.rand_noise(),
+                    NoiseType::White => self.noise.white_noise(),
+                };
+            }
+            self.producer.push(*value).unwrap();
+            if !self.view_producer.is_full() {
+                self.view_producer.push(*value).unwrap();
             }
+            self.tick += 1;
         }
         // Input
         if let Some(input) = self.input_consumer.pop() {
@@ -3,7 +3,11 @@ use crate::generator::Generator;
 use eframe::egui;
 use egui::Color32;
 use egui_plot::{Line, Plot, PlotPoints};
+use kopek::fft::RfftPlan;
+use kopek::spectrum;
 use kopek::utils::{self};
+use kopek::window::{Window, WindowType};
+use num::complex::Complex;
 use ringbuf::{HeapConsumer, HeapProducer, HeapRb};
 use std::collections::VecDeque;
 
@@ -14,6 +18,12 @@ pub struct View {
     audio_model: AudioModel,
     input_producer: HeapProducer<Input>, // first is freq, second is octave
     sample: VecDeque<f32>,
+    // Kept between frames so the spectrum doesn't allocate every frame
+    fft_plan: RfftPlan<f32>,
+    fft_window: Window,
+    fft_input: Vec<f32>,
+    fft_scratch: Vec<Complex<f32>>,
+    fft_output: Vec<Complex<f32>>,
     view_consumer: HeapConsumer<f32>,
     selected_noise: u8,
     selected_wave: u8,
@@ -31,6 +41,7 @@ impl Default for View {
         let (view_producer, view_consumer) = view_ring.split();
         let audio_model = AudioModel::new(consumer).unwrap();
         let sample_rate = audio_model.sample_rate;
+        let fft_plan = RfftPlan::new(1024);
         std::thread::spawn(move || {
             let mut generator =
                 Generator::new(producer, input_consumer, view_producer, sample_rate).unwrap();
@@ -42,6 +53,11 @@ impl Default for View {
             audio_model,
             input_producer,
             sample: VecDeque::from([0.0; 1024]),
+            fft_window: Window::new(WindowType::Hann, fft_plan.size()),
+            fft_input: vec![0.0; fft_plan.size()],
+            fft_scratch: vec![Complex::new(0.0, 0.0); fft_plan.scratch_len()],
+            fft_output: vec![Complex::new(0.0, 0.0); fft_plan.spectrum_len()],
+            fft_plan,
             view_consumer,
             selected_noise: 0,
             selected_wave: 0,
@@ -137,22 +153,28 @@ impl eframe::App for View {
             self.sample.make_contiguous();
             let waveform_line = Line::new(PlotPoints::from_ys_f32(&self.sample.as_slices().0));
 
-            let fft_input: Vec<_> = self
-                .sample
-                .iter()
-                .map(|s| std::convert::From::from(*s as f64 / std::i16::MAX as f64))
-                .collect();
-            let fft_output = kopek::fft::fft(&fft_input);
+            for (input, &sample) in self.fft_input.iter_mut().zip(&self.sample) {
+                *input = sample;
+            }
+            self.fft_window.apply(&mut self.fft_input);
+            self.fft_plan.forward_with_scratch(
+                &self.fft_input,
+                &mut self.fft_output,
+                &mut self.fft_scratch,
+            );
+            let fft_output = &self.fft_output;
 
-            let half = fft_output.len() / 8;
-            let bin_width = 48_000.0 / fft_output.len() as f64;
+            // rfft only returns the bins up to Nyquist, so this is the same eighth as before
+            let half = fft_output.len() / 4;
+            let sample_rate = self.audio_model.sample_rate as u32;
+            let fft_size = self.fft_plan.size();
 
             let points: Vec<[f64; 2]> = fft_output[..half]
                 .iter()
                 .enumerate()
                 .map(|(i, complex)| {
-                    let freq = i as f64 * bin_width;
-                    let magnitude = complex.norm(); // or 20.0 * log10(norm()) if dB scale
+                    let freq = spectrum::bin_to_frequency(i, sample_rate, fft_size) as f64;
+                    let magnitude = complex.norm() as f64; // or 20.0 * log10(norm()) if dB scale
                     [freq, magnitude]
                 })
                 .collect();
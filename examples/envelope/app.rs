@@ -35,17 +35,13 @@ impl App {
     }
 
     pub fn update(&mut self) {
-        for _ in 0..1024 {
-            if !self.producer.is_full() {
-                let mut value = 0.0;
-                self.oscillator.set_frequency(C_FREQ * 4.0);
-                value += self.oscillator.sine();
-
-                value *= self.envelope.update();
-
-                self.producer.push(value).unwrap();
-                self.tick += 1.0;
-            }
+        let mut block = [0.0; 1024];
+        let block = &mut block[..self.producer.free_len().min(1024)];
+        self.oscillator.set_frequency(C_FREQ * 4.0);
+        self.oscillator.process(block, None);
+        for value in block.iter() {
+            self.producer.push(value * self.envelope.update()).unwrap();
+            self.tick += 1.0;
         }
 
         while let Some(message) = self.input_consumer.pop() {
@@ -1,4 +1,3 @@
 pub const SCREEN_WIDTH: u32 = 1100;
 pub const SCREEN_HEIGHT: u32 = 600;
-pub const BIN_SIZE: f32 = 44100.0 / 1024.0; // Maybe 22050.0 / 512.0 is more logical
 pub const X_SCALE: f32 = 4.0;
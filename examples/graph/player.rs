@@ -4,6 +4,9 @@ use cpal::{
     traits::{DeviceTrait, HostTrait, StreamTrait},
     Device, Host, StreamConfig,
 };
+use kopek::fft::RfftPlan;
+use kopek::window::{Window, WindowType};
+use num::complex::Complex;
 use std::sync::mpsc::{Receiver, Sender};
 
 pub const PATHS: [&str; 11] = [
@@ -26,7 +29,16 @@ pub struct Player {
     receiver: Receiver<Vec<[f32; 2]>>,
     waveform_graph_points: Vec<Point2>,
     frequency_graph_points: Vec<Point2>,
+    octave_bar_points: Vec<Point2>,
     track: Vec<f32>,
+    // Of the stream started last, the spectrum bins depend on it
+    sample_rate: u32,
+    // Kept between updates so the spectrum doesn't allocate every frame
+    fft_plan: RfftPlan<f32>,
+    fft_window: Window,
+    fft_input: Vec<f32>,
+    fft_scratch: Vec<Complex<f32>>,
+    fft_output: Vec<Complex<f32>>,
 }
 
 impl Player {
@@ -34,6 +46,7 @@ impl Player {
         let audio_host = cpal::default_host();
 
         let (sender, receiver) = std::sync::mpsc::channel::<Vec<[f32; 2]>>();
+        let fft_plan = RfftPlan::new(1024);
 
         Player {
             audio_host,
@@ -41,7 +54,14 @@ impl Player {
             receiver,
             waveform_graph_points: vec![],
             frequency_graph_points: vec![],
+            octave_bar_points: vec![],
             track: Player::load_track_at_path(PATHS[0]), // output_stream: create_output_stream(),
+            sample_rate: 44100,
+            fft_window: Window::new(WindowType::Hann, fft_plan.size()),
+            fft_input: vec![0.0; fft_plan.size()],
+            fft_scratch: vec![Complex::new(0.0, 0.0); fft_plan.scratch_len()],
+            fft_output: vec![Complex::new(0.0, 0.0); fft_plan.spectrum_len()],
+            fft_plan,
         }
     }
 
@@ -53,12 +73,23 @@ impl Player {
         }
 
         // println!("received: {:?}", frames[0]);
-        let fft_input: Vec<_> = frames
-            .iter()
-            .map(|frame| std::convert::From::from(frame[0] as f64 / std::i16::MAX as f64))
-            .collect();
-
-        let fft_output = kopek::fft::fft(&fft_input);
+        // New buffers only when the device delivers a different buffer size
+        if self.fft_plan.size() != frames.len() {
+            self.fft_plan = RfftPlan::new(frames.len());
+            self.fft_window = Window::new(WindowType::Hann, frames.len());
+            self.fft_input = vec![0.0; frames.len()];
+            self.fft_scratch = vec![Complex::new(0.0, 0.0); self.fft_plan.scratch_len()];
+            self.fft_output = vec![Complex::new(0.0, 0.0); self.fft_plan.spectrum_len()];
+        }
+        for (input, frame) in self.fft_input.iter_mut().zip(&frames) {
+            *input = frame[0];
+        }
+        self.fft_window.apply(&mut self.fft_input);
+        self.fft_plan.forward_with_scratch(
+            &self.fft_input,
+            &mut self.fft_output,
+            &mut self.fft_scratch,
+        );
 
         if frames.len() > 0 {
             let frame_slice = frames
@@ -66,7 +97,12 @@ impl Player {
                 .map(|frame| 30.0 + frame[0] as f32 * 20.0)
                 .collect();
             self.waveform_graph_points = utils::get_waveform_graph(&frame_slice, 1.0);
-            self.frequency_graph_points = utils::get_frequency_domain_graph(&fft_output, 1.0);
+            self.frequency_graph_points = utils::get_frequency_domain_graph(&self.fft_output, 1.0);
+            self.octave_bar_points = utils::get_octave_bar_spectrum(
+                &self.fft_output,
+                self.sample_rate,
+                self.fft_plan.size(),
+            );
             // self.scale_points = utils::get_scale(128);
         }
     }
@@ -82,8 +118,8 @@ impl Player {
     where
         P: AsRef<std::path::Path>,
     {
-        let frames = kopek::decoder::decode(path);
-        let volume_factor = 0.00002;
+        let frames = kopek::decoder::decode_f32(path).frames;
+        let volume_factor = 0.65;
         let frames: Vec<f32> = frames
             .iter()
             .map(|frame| {
@@ -101,7 +137,7 @@ impl Player {
         frames
     }
 
-    pub fn record(&self) {
+    pub fn record(&mut self) {
         let input_device = self
             .audio_host
             .default_input_device()
@@ -111,6 +147,7 @@ impl Player {
             "channels: {:?}, sample rate: {:?}",
             input_config.channels, input_config.sample_rate
         );
+        self.sample_rate = input_config.sample_rate.0;
 
         let sender = self.sender.clone();
         std::thread::spawn(move || {
@@ -120,7 +157,7 @@ impl Player {
         });
     }
 
-    pub fn play(&self) {
+    pub fn play(&mut self) {
         let output_device = self
             .audio_host
             .default_output_device()
@@ -130,6 +167,7 @@ impl Player {
             "channels: {:?}, sample rate: {:?}",
             output_config.channels, output_config.sample_rate,
         );
+        self.sample_rate = output_config.sample_rate.0;
 
         let sender = self.sender.clone();
         let track = self.track.clone();
@@ -149,6 +187,10 @@ impl Player {
     pub fn get_frequency_graph_points(&self) -> &Vec<Point2> {
         &self.frequency_graph_points
     }
+
+    pub fn get_octave_bar_points(&self) -> &Vec<Point2> {
+        &self.octave_bar_points
+    }
 }
 
 fn create_input_stream(
@@ -1,7 +1,7 @@
 use super::utils::Point2;
 use eframe::egui;
 use egui::Color32;
-use egui_plot::{Line, Plot, PlotPoints};
+use egui_plot::{Bar, BarChart, Line, Plot, PlotPoints};
 
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
 #[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
@@ -70,7 +70,13 @@ impl eframe::App for AnalysisView {
                 Color32::from_rgb(100, 200, 100),
             );
 
+            let octave_bars = bars_from_points(
+                &self.player.get_octave_bar_points(),
+                Color32::from_rgb(100, 100, 200),
+            );
+
             Plot::new("waveform").show(ui, |plot_ui| {
+                plot_ui.bar_chart(octave_bars);
                 plot_ui.line(frequency_line);
                 plot_ui.line(waveform_line)
             });
@@ -130,6 +136,17 @@ fn line_from_points(points: &Vec<Point2>, color: Color32) -> Line {
     line
 }
 
+// The lines are plotted against their index, so the bars are moved from the screen
+// coordinates of the points to the same range
+fn bars_from_points(points: &Vec<Point2>, color: Color32) -> BarChart {
+    let bars = points
+        .iter()
+        .map(|p| Bar::new((p.x + 512.0) as f64, p.y as f64).width(80.0))
+        .collect();
+
+    BarChart::new(bars).color(color).name("octaves")
+}
+
 impl AnalysisView {
     #[deprecated]
     fn _sin(&self) -> Line {
@@ -21,6 +21,7 @@ const LATENCY_MS: f32 = 10.0;
 pub struct Model {
     pub input_stream: Stream,
     pub consumer: HeapConsumer<f32>,
+    pub sample_rate: u32,
 }
 
 impl Model {
@@ -92,6 +93,7 @@ impl Model {
         Ok(Model {
             input_stream,
             consumer,
+            sample_rate: config.sample_rate.0,
         })
     }
 }
@@ -2,6 +2,7 @@ use bevy::{
     prelude::*,
     sprite::collide_aabb::{collide, Collision},
 };
+use kopek::band_energy::{BandEnergyConfig, BandEnergyTracker};
 mod utils;
 
 mod audio;
@@ -10,7 +11,16 @@ fn main() {
     let audio_model = audio::Model::new();
     if let Ok(am) = audio_model {
         // am.output_stream.pause();
-        let game = Game { audio_model: am };
+        // 8 bands between about 860 Hz and 1900 Hz, where whistling is
+        let config = BandEnergyConfig {
+            bands: kopek::spectrum::log_bands(860.0, 1900.0, 8),
+            floor_db: -40.0,
+            ..Default::default()
+        };
+        let game = Game {
+            tracker: BandEnergyTracker::new(config, am.sample_rate),
+            audio_model: am,
+        };
         App::new()
             .add_plugins(DefaultPlugins)
             .insert_non_send_resource(game)
@@ -173,45 +183,24 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
 }
 
 fn local_resource_controller(world: &mut World) {
-    let mut samples = vec![];
-    for _ in 0..1024 {
-        let sample = match world
-            .get_non_send_resource_mut::<Game>()
-            .unwrap()
-            .audio_model
-            .consumer
-            .pop()
-        {
-            Some(s) => s,
-            None => 0.0,
-        };
-
-        samples.push(sample);
-    }
-
-    let fft_input: Vec<_> = samples
-        .iter()
-        .map(|frame| std::convert::From::from(*frame as f64))
-        .collect();
-    let fft_output = kopek::fft::fft(&fft_input);
-    let frequency_domain = utils::get_frequency_domain_graph(&fft_output, 1.0);
-    let average_bins = utils::get_narrow_bar_spectrum_low(&frequency_domain);
-
-    let mut max = 0.0;
-    let mut index = 0;
-    for (i, bin) in average_bins.iter().enumerate() {
-        if *bin > max {
-            max = *bin;
-            index = i;
+    let (factor, levels) = {
+        let mut game = world.get_non_send_resource_mut::<Game>().unwrap();
+        let mut samples = vec![];
+        while let Some(sample) = game.audio_model.consumer.pop() {
+            samples.push(sample);
         }
-    }
+        game.tracker.process(&samples);
 
-    world.get_resource_mut::<TargetPosition>().unwrap().factor = if max > 50.0 {
-        -1.0 + index as f32 * 0.5
-    } else {
-        0.0
+        // Whistling higher moves the paddle up
+        let factor = match game.tracker.loudest_band() {
+            Some(index) if game.tracker.levels()[index] > 0.5 => -1.0 + index as f32 * 0.5,
+            _ => 0.0,
+        };
+        (factor, game.tracker.levels().to_vec())
     };
-    world.get_resource_mut::<SpectrumData>().unwrap().averages = average_bins;
+
+    world.get_resource_mut::<TargetPosition>().unwrap().factor = factor;
+    world.get_resource_mut::<SpectrumData>().unwrap().averages = levels;
 }
 
 fn spectrum_bar_system(
@@ -221,7 +210,7 @@ fn spectrum_bar_system(
     if spectrum_data.averages.len() == 8 {
         let mut i = 0;
         for (_, mut transform) in query.iter_mut() {
-            transform.scale.x = spectrum_data.averages[i] / 100.0;
+            transform.scale.x = spectrum_data.averages[i];
             i += 1;
         }
     }
@@ -375,6 +364,7 @@ enum Player {
 
 struct Game {
     audio_model: audio::Model,
+    tracker: BandEnergyTracker,
 }
 
 #[derive(Resource)]
@@ -1,7 +1,4 @@
 use bevy::math::{vec2, Vec2};
-use num::complex::Complex;
-
-const BIN_SIZE: f32 = 44100.0 / 1024.0;
 
 // The representable range is 22050 if sample rate is 44100
 // Frequency bin size is for each element in the output vector
@@ -30,85 +27,3 @@ pub fn get_waveform_graph(frame_slice: &Vec<f32>, scale: f32) -> Vec<Vec2> {
 
     waveform_points
 }
-
-// Complex FFT gives z = x + jy, where x is the real part and y is the imaginary part
-// Magnitude, |z| = sqrt(x^2 + y^2)
-pub fn get_frequency_domain_graph(fft_output: &Vec<Complex<f64>>, x_scale: f32) -> Vec<Vec2> {
-    let frequency_graph_points: Vec<Vec2> = fft_output
-        .iter()
-        .map(|c| {
-            let magnitude = ((c.re as f32).powf(2.0) + (c.im as f32).powf(2.0)).sqrt();
-            let p = vec2(0.0, magnitude * 100.0);
-            p
-        })
-        .collect();
-
-    frequency_graph_points
-}
-
-pub fn get_narrow_bar_spectrum_scale() -> Vec<Vec2> {
-    let bin_sizes: Vec<i32> = vec![4, 4, 8, 16, 32, 64, 128, 256];
-    let mut accumulator = 0.0;
-    let bin_ranges: Vec<Vec2> = bin_sizes
-        .iter()
-        .map(|bin_size| {
-            let start_freq = accumulator;
-            let end_freq = start_freq + BIN_SIZE * *bin_size as f32;
-            accumulator = end_freq;
-            vec2(start_freq, end_freq)
-        })
-        .collect();
-
-    bin_ranges
-}
-
-// Returns bar spectrum like the old cd players
-pub fn get_narrow_bar_spectrum(frequency_line_points: &Vec<Vec2>) -> Vec<Vec2> {
-    // implement another view to have non-linear bin sizes
-    // e.g. 32-64-125-250-500-1k-2k-4k-8k-16k Hz
-    // get half of model.frequency_line_points
-
-    // println!("bin_sizes: {:?}", bin_sizes);
-
-    let bin_sizes: Vec<i32> = vec![4, 4, 8, 16, 32, 64, 128, 256];
-    // After this bin sizes are 4, 4, 8, 16, 32, 64, 128, 256. In total 512 data points, half of frequency_line_points
-    // The other half is kind of a reflection
-    // If sample rate is 44100, each data point will represent 44100 / 1024 = 43.07Hz
-    let mut average_bins: Vec<Vec2> = vec![];
-    let mut start_index = 0;
-    for (i, bin_size) in bin_sizes.into_iter().enumerate() {
-        let end_index = start_index + bin_size;
-        let sum: f32 = frequency_line_points[start_index as usize..end_index as usize]
-            .iter()
-            .map(|v| v.y)
-            .sum();
-        let average = sum / bin_size as f32;
-
-        // println!("{}, {}, {}", start_index, end_index, sum);
-        // println!("{} {} average: {}", start_index, end_index, average);
-        average_bins.push(vec2(-462.0 + 100.0 * i as f32, average));
-        start_index += bin_size;
-    }
-
-    average_bins
-}
-
-pub fn get_narrow_bar_spectrum_low(frequency_line_points: &Vec<Vec2>) -> Vec<f32> {
-    let bin_sizes: Vec<i32> = vec![3, 3, 3, 3, 3, 3, 3, 3];
-
-    let mut average_bins: Vec<f32> = vec![];
-    let mut start_index = 20;
-    for (i, bin_size) in bin_sizes.into_iter().enumerate() {
-        let end_index = start_index + bin_size;
-        let sum: f32 = frequency_line_points[start_index as usize..end_index as usize]
-            .iter()
-            .map(|v| v.y)
-            .sum();
-        let average = sum / bin_size as f32;
-
-        average_bins.push(average);
-        start_index += bin_size;
-    }
-
-    average_bins
-}
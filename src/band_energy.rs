@@ -0,0 +1,246 @@
+use crate::fft::RfftPlan;
+use crate::spectrum::{self, Aggregation, Band};
+use crate::window::{Window, WindowType};
+use num::complex::Complex;
+use num::Zero;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BandEnergyConfig {
+    pub bands: Vec<Band>,
+    pub frame_size: usize,
+    pub hop_size: usize,
+    pub window: WindowType,
+    // Time constants of the smoothed energy in seconds, rising and falling
+    pub attack: f32,
+    pub release: f32,
+    // Levels are relative to the loudest recent energy of each band, which fades over
+    // peak_release seconds so quieter passages still fill the 0..1 range
+    pub peak_release: f32,
+    // A band hits when its energy rises above hit_threshold times its average over the
+    // last average_time seconds
+    pub hit_threshold: f32,
+    pub average_time: f32,
+    // Shortest time between two hits of the same band in seconds
+    pub hit_cooldown: f32,
+    // Energy in dBFS below which a band counts as silent, it gets level 0 and never hits
+    pub floor_db: f32,
+}
+
+impl Default for BandEnergyConfig {
+    // Octave bands over the audible range, about 12 ms between updates at 44100 Hz
+    fn default() -> Self {
+        Self {
+            bands: spectrum::octave_bands(31.5, 16_000.0),
+            frame_size: 1024,
+            hop_size: 512,
+            window: WindowType::Hann,
+            attack: 0.01,
+            release: 0.2,
+            peak_release: 3.0,
+            hit_threshold: 2.0,
+            average_time: 1.0,
+            hit_cooldown: 0.1,
+            floor_db: -60.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BandHit {
+    pub band: usize,
+    // Samples processed when the hit was detected, counted from the first process call
+    pub sample: u64,
+    // Energy over the threshold, 1 is just on it
+    pub strength: f32,
+}
+
+// Keeps track of the energy in a set of frequency bands of a stream of mono samples, fed
+// in blocks of any length
+pub struct BandEnergyTracker {
+    config: BandEnergyConfig,
+    sample_rate: u32,
+    window: Window,
+    plan: RfftPlan<f32>,
+    buffer: Vec<f32>,
+    spectrum: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    // Samples not analysed yet, the last frame_size - hop_size of the previous frame first
+    pending: Vec<f32>,
+    processed: u64,
+    energies: Vec<f32>,
+    levels: Vec<f32>,
+    peaks: Vec<f32>,
+    averages: Vec<f32>,
+    above: Vec<bool>,
+    // Seconds until each band may hit again
+    cooldowns: Vec<f32>,
+}
+
+impl BandEnergyTracker {
+    pub fn new(config: BandEnergyConfig, sample_rate: u32) -> Self {
+        assert!(
+            config.hop_size > 0 && config.hop_size <= config.frame_size,
+            "hop_size must be positive and at most frame_size"
+        );
+        let band_count = config.bands.len();
+        let plan = RfftPlan::new(config.frame_size);
+        Self {
+            window: Window::new(config.window, config.frame_size),
+            buffer: vec![0.0; config.frame_size],
+            spectrum: vec![Complex::zero(); plan.spectrum_len()],
+            scratch: vec![Complex::zero(); plan.scratch_len()],
+            plan,
+            pending: Vec::with_capacity(2 * config.frame_size),
+            processed: 0,
+            energies: vec![0.0; band_count],
+            levels: vec![0.0; band_count],
+            peaks: vec![0.0; band_count],
+            averages: vec![0.0; band_count],
+            above: vec![false; band_count],
+            cooldowns: vec![0.0; band_count],
+            config,
+            sample_rate,
+        }
+    }
+
+    pub fn config(&self) -> &BandEnergyConfig {
+        &self.config
+    }
+
+    pub fn bands(&self) -> &[Band] {
+        &self.config.bands
+    }
+
+    // Smoothed energy of every band relative to full scale, a full scale sine in a band
+    // reads about 1
+    pub fn energies(&self) -> &[f32] {
+        &self.energies
+    }
+
+    pub fn energies_db(&self) -> Vec<f32> {
+        self.energies
+            .iter()
+            .map(|&energy| 10.0 * energy.max(f32::MIN_POSITIVE).log10())
+            .collect()
+    }
+
+    // Energy of every band scaled to 0..1 by its recent peak
+    pub fn levels(&self) -> &[f32] {
+        &self.levels
+    }
+
+    // Band with the most energy, None when all of them are below the floor
+    pub fn loudest_band(&self) -> Option<usize> {
+        let floor = self.floor();
+        self.energies
+            .iter()
+            .enumerate()
+            .filter(|(_, &energy)| energy > floor)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i)
+    }
+
+    // Analyses every whole hop in samples and returns the hits found, in order
+    pub fn process(&mut self, samples: &[f32]) -> Vec<BandHit> {
+        let (frame_size, hop_size) = (self.config.frame_size, self.config.hop_size);
+        let mut hits = vec![];
+        for &sample in samples {
+            self.pending.push(sample);
+            self.processed += 1;
+            if self.pending.len() == frame_size {
+                self.analyse(&mut hits);
+                self.pending.drain(..hop_size);
+            }
+        }
+        hits
+    }
+
+    // Forgets all the samples and levels, e.g. when the music changes
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.processed = 0;
+        for values in [
+            &mut self.energies,
+            &mut self.levels,
+            &mut self.peaks,
+            &mut self.averages,
+            &mut self.cooldowns,
+        ] {
+            values.fill(0.0);
+        }
+        self.above.fill(false);
+    }
+
+    fn floor(&self) -> f32 {
+        10.0_f32.powf(self.config.floor_db / 10.0)
+    }
+
+    fn analyse(&mut self, hits: &mut Vec<BandHit>) {
+        let frame_size = self.config.frame_size;
+        self.buffer.copy_from_slice(&self.pending[..frame_size]);
+        self.window.apply(&mut self.buffer);
+        self.plan
+            .forward_with_scratch(&self.buffer, &mut self.spectrum, &mut self.scratch);
+
+        // Power relative to the peak bin of a full scale sine
+        let reference = frame_size as f32 * self.window.coherent_gain() as f32 / 2.0;
+        let powers: Vec<f32> = spectrum::power_spectrum(&self.spectrum)
+            .into_iter()
+            .map(|power| power / (reference * reference))
+            .collect();
+        // The bins of a sine's main lobe add up to the window's ENBW
+        let enbw = self.window.enbw() as f32;
+        let raw = spectrum::aggregate_bands(
+            &powers,
+            &self.config.bands,
+            self.sample_rate,
+            frame_size,
+            Aggregation::Sum,
+        );
+
+        let dt = self.config.hop_size as f32 / self.sample_rate as f32;
+        let coefficient = |time: f32| match time > 0.0 {
+            true => 1.0 - (-dt / time).exp(),
+            false => 1.0,
+        };
+        let (attack, release) = (
+            coefficient(self.config.attack),
+            coefficient(self.config.release),
+        );
+        let average = coefficient(self.config.average_time);
+        let peak_decay = 1.0 - coefficient(self.config.peak_release);
+        let floor = self.floor();
+
+        for (band, raw) in raw.into_iter().enumerate() {
+            let raw = raw / enbw;
+            let energy = &mut self.energies[band];
+            *energy += match raw > *energy {
+                true => attack,
+                false => release,
+            } * (raw - *energy);
+            let energy = *energy;
+
+            self.peaks[band] = (self.peaks[band] * peak_decay).max(energy).max(floor);
+            self.levels[band] = match energy > floor {
+                true => (energy / self.peaks[band]).min(1.0),
+                false => 0.0,
+            };
+
+            self.cooldowns[band] = (self.cooldowns[band] - dt).max(0.0);
+            let threshold = (self.averages[band] * self.config.hit_threshold).max(floor);
+            let above = energy > threshold;
+            if above && !self.above[band] && self.cooldowns[band] == 0.0 {
+                hits.push(BandHit {
+                    band,
+                    sample: self.processed,
+                    strength: energy / threshold,
+                });
+                self.cooldowns[band] = self.config.hit_cooldown;
+            }
+            self.above[band] = above;
+            // The average follows the energy from the frame after, so a hit is judged
+            // against what came before it
+            self.averages[band] += average * (energy - self.averages[band]);
+        }
+    }
+}
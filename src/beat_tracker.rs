@@ -0,0 +1,192 @@
+use crate::decoder::{DecodedAudio, DecoderSample};
+use crate::metronome::Metronome;
+use crate::tempo::{self, OnsetEnvelope};
+use crate::time_signature::TimeSignature;
+
+// How strongly beats are kept on the tempo grid, higher values allow less tempo drift
+const TIGHTNESS: f32 = 100.0;
+
+#[derive(Debug, Clone)]
+pub struct BeatTrack {
+    pub bpm: f32,
+    pub sample_rate: u32,
+    pub beats_per_bar: u8,
+    // Frame offsets into the decoded audio
+    pub beats: Vec<u64>,
+    pub downbeats: Vec<u64>,
+}
+
+impl BeatTrack {
+    // Average distance between beats in frames
+    pub fn beat_period(&self) -> f32 {
+        self.sample_rate as f32 * 60.0 / self.bpm
+    }
+
+    // A metronome that ticks on the tracked beats, elapsed samples are counted from the
+    // start of the decoded audio like in Metronome::update
+    pub fn metronome(&self, channel_count: u32) -> Metronome {
+        let mut metronome =
+            Metronome::new(self.bpm.round() as u16, self.sample_rate, channel_count);
+        metronome.set_tempo(self.bpm);
+        // Start counting as early as possible but a whole number of bars before the first
+        // downbeat, so the beat index of every downbeat is a multiple of beats_per_bar.
+        // The fitted grid is used instead of the first downbeat, it has less jitter
+        let (start, period) = fit_grid(&self.beats).unwrap_or((0.0, self.beat_period()));
+        let phase = self
+            .beats
+            .iter()
+            .position(|b| self.downbeats.first() == Some(b))
+            .unwrap_or_default();
+        let first_downbeat = (start + phase as f32 * period).max(0.0);
+        let offset = first_downbeat % (period * self.beats_per_bar as f32);
+        metronome.set_offset((offset * channel_count as f32).round() as u32);
+
+        metronome
+    }
+
+    pub fn time_signature(&self, note_value: u8, channel_count: u16) -> TimeSignature {
+        TimeSignature::from_metronome(
+            (self.beats_per_bar, note_value),
+            self.metronome(channel_count as u32),
+        )
+    }
+}
+
+// Picks the tempo with tempo::estimate_tempo, then places beats on the strongest onsets that
+// keep close to that tempo. None if no tempo can be found
+pub fn track_beats<S: DecoderSample>(
+    audio: &DecodedAudio<S>,
+    beats_per_bar: u8,
+) -> Option<BeatTrack> {
+    let envelope = tempo::default_onset_envelope(&audio.to_mono(), audio.sample_rate);
+    let estimated_bpm = tempo::estimate_tempo_from_envelope(&envelope).first()?.bpm;
+    let beat_frames = track_envelope(&envelope, estimated_bpm);
+    let beat_values: Vec<f32> = beat_frames.iter().map(|&i| envelope.values[i]).collect();
+    // A flux frame compares a window to the previous one, so an onset is detected about
+    // one hop before the window ends
+    let to_sample =
+        |i: usize| (i * envelope.hop_size + envelope.frame_size - envelope.hop_size) as u64;
+    let beats: Vec<u64> = beat_frames.iter().map(|&i| to_sample(i)).collect();
+    // The tracked beats give a finer tempo than the envelope frame rate allows
+    let bpm = match fit_grid(&beats) {
+        Some((_, period)) if period > 0.0 => audio.sample_rate as f32 * 60.0 / period,
+        _ => estimated_bpm,
+    };
+
+    // The bar phase whose beats have the strongest onsets is taken as the downbeat
+    let beats_per_bar = beats_per_bar.max(1);
+    let phase = (0..beats_per_bar as usize)
+        .map(|phase| {
+            let strength: f32 = beat_values
+                .iter()
+                .skip(phase)
+                .step_by(beats_per_bar as usize)
+                .sum();
+            let count = beat_values
+                .len()
+                .saturating_sub(phase)
+                .div_ceil(beats_per_bar as usize);
+            (phase, strength / count.max(1) as f32)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(phase, _)| phase)
+        .unwrap_or_default();
+    let downbeats = beats
+        .iter()
+        .skip(phase)
+        .step_by(beats_per_bar as usize)
+        .copied()
+        .collect();
+
+    Some(BeatTrack {
+        bpm,
+        sample_rate: audio.sample_rate,
+        beats_per_bar,
+        beats,
+        downbeats,
+    })
+}
+
+// Least squares fit of beat = start + index * period, None for less than two beats
+fn fit_grid(beats: &[u64]) -> Option<(f32, f32)> {
+    if beats.len() < 2 {
+        return None;
+    }
+    let n = beats.len() as f64;
+    let mean_index = (n - 1.0) / 2.0;
+    let mean_beat = beats.iter().map(|&b| b as f64).sum::<f64>() / n;
+    let (covariance, variance) = beats
+        .iter()
+        .enumerate()
+        .fold((0.0, 0.0), |(c, v), (i, &b)| {
+            let di = i as f64 - mean_index;
+            (c + di * (b as f64 - mean_beat), v + di * di)
+        });
+    let period = covariance / variance;
+
+    Some(((mean_beat - period * mean_index) as f32, period as f32))
+}
+
+// Dynamic programming beat tracker (Ellis, 2007): every frame gets the best score of a beat
+// sequence ending on it, which is its onset strength plus the best previous beat, penalized
+// by how far the gap is from the beat period. Returns envelope frame indices
+fn track_envelope(envelope: &OnsetEnvelope, bpm: f32) -> Vec<usize> {
+    let values = &envelope.values;
+    let period = 60.0 * envelope.frame_rate() / bpm;
+    let mean = values.iter().sum::<f32>() / values.len().max(1) as f32;
+    let deviation = (values.iter().map(|v| (v - mean).powi(2)).sum::<f32>()
+        / values.len().max(1) as f32)
+        .sqrt();
+    if deviation <= 0.0 {
+        return vec![];
+    }
+    let onsets: Vec<f32> = values.iter().map(|v| v / deviation).collect();
+
+    let mut scores = vec![0.0_f32; onsets.len()];
+    let mut previous: Vec<Option<usize>> = vec![None; onsets.len()];
+    let (min_gap, max_gap) = (
+        (period / 2.0).round() as usize,
+        (period * 2.0).round() as usize,
+    );
+    for t in 0..onsets.len() {
+        let best = (t.saturating_sub(max_gap)..(t + 1).saturating_sub(min_gap.max(1)))
+            .map(|p| {
+                let gap = ((t - p) as f32 / period).ln();
+                (p, scores[p] - TIGHTNESS * gap * gap)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        scores[t] = onsets[t];
+        if let Some((p, score)) = best {
+            if score > 0.0 {
+                scores[t] += score;
+                previous[t] = Some(p);
+            }
+        }
+    }
+
+    // The sequence ends on the best scoring frame of the last beat period
+    let last_period = onsets.len().saturating_sub(period.round() as usize);
+    let Some(mut t) = (last_period..onsets.len()).max_by(|&a, &b| scores[a].total_cmp(&scores[b]))
+    else {
+        return vec![];
+    };
+    let mut beats = vec![t];
+    while let Some(p) = previous[t] {
+        beats.push(p);
+        t = p;
+    }
+    beats.reverse();
+
+    // Drop the beats placed in silence before the music starts and after it ends
+    let threshold = 0.5 * beats.iter().map(|&b| onsets[b]).sum::<f32>() / beats.len() as f32;
+    let first = beats
+        .iter()
+        .position(|&b| onsets[b] > threshold)
+        .unwrap_or(0);
+    let last = beats
+        .iter()
+        .rposition(|&b| onsets[b] > threshold)
+        .unwrap_or(0);
+
+    beats[first..=last.max(first)].to_vec()
+}
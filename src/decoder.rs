@@ -1,71 +1,520 @@
-pub fn decode<P>(path: P) -> Vec<[i16; 2]>
+use crate::resample::{self, ResampleType};
+use audrey::dasp_sample::{FromSample, ToSample};
+use audrey::read::{FormatError, ReadError};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum DecodeError {
+    FileNotFound(PathBuf),
+    Io(std::io::Error),
+    UnsupportedFormat,
+    // The channel positions of the source are unknown, so it can't be downmixed
+    UnsupportedChannelLayout(u32),
+    // The file was opened but reading a sample failed part way through
+    Read(FormatError),
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::FileNotFound(path) => write!(f, "File not found: {}", path.display()),
+            DecodeError::Io(err) => write!(f, "IO error: {}", err),
+            DecodeError::UnsupportedFormat => write!(f, "Unsupported audio format"),
+            DecodeError::UnsupportedChannelLayout(channel_count) => {
+                write!(f, "Unsupported channel count: {}", channel_count)
+            }
+            DecodeError::Read(err) => write!(f, "Error while reading samples: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::Io(err) => Some(err),
+            DecodeError::Read(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<FormatError> for DecodeError {
+    fn from(err: FormatError) -> Self {
+        DecodeError::Read(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    Int(u32), // bits per sample
+    Float(u32),
+    // Lossy formats like Ogg Vorbis don't have a bit depth
+    Unknown,
+}
+
+// Sample types the decoder can produce. Integer samples keep their native range,
+// floating point samples are normalized to [-1, 1]
+pub trait DecoderSample: audrey::read::Sample + FromSample<f64> + ToSample<f64> {}
+
+impl<T> DecoderSample for T where T: audrey::read::Sample + FromSample<f64> + ToSample<f64> {}
+
+#[derive(Debug, Clone)]
+pub struct DecodedAudio<S = i16> {
+    // Mono sources are duplicated to both channels
+    pub frames: Vec<[S; 2]>,
+    pub sample_rate: u32,
+    // Channel count of the source, not of the frames
+    pub channel_count: u32,
+    pub format: audrey::Format,
+    pub sample_format: SampleFormat,
+}
+
+impl<S: DecoderSample> DecodedAudio<S> {
+    // Average of both channels, normalized to [-1, 1]
+    pub fn to_mono(&self) -> Vec<f32> {
+        self.frames
+            .iter()
+            .map(|&[left, right]| {
+                let (left, right): (f64, f64) = (left.to_sample_(), right.to_sample_());
+                ((left + right) / 2.0) as f32
+            })
+            .collect()
+    }
+
+    // Converts the frames to sample_rate, the source description is kept as it is
+    pub fn resample(&self, sample_rate: u32, resample_type: ResampleType) -> DecodedAudio<S> {
+        let frames: Vec<[f32; 2]> = self
+            .frames
+            .iter()
+            .map(|&[left, right]| {
+                let (left, right): (f64, f64) = (left.to_sample_(), right.to_sample_());
+                [left as f32, right as f32]
+            })
+            .collect();
+        let frames =
+            resample::resample_frames(&frames, self.sample_rate, sample_rate, resample_type)
+                .into_iter()
+                .map(|[left, right]| [S::from_sample_(left as f64), S::from_sample_(right as f64)])
+                .collect();
+
+        DecodedAudio {
+            frames,
+            sample_rate,
+            channel_count: self.channel_count,
+            format: self.format,
+            sample_format: self.sample_format,
+        }
+    }
+}
+
+// Speaker positions, used to pick downmix coefficients
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    FrontLeft,
+    FrontRight,
+    FrontCenter,
+    Lfe,
+    BackLeft,
+    BackRight,
+    BackCenter,
+    SideLeft,
+    SideRight,
+    Unknown,
+}
+
+impl Channel {
+    // (left, right) gains when downmixing to stereo, center and surround channels
+    // are mixed at -3 dB as in ITU-R BS.775, LFE is dropped
+    fn stereo_coefficients(&self) -> Option<(f64, f64)> {
+        const MINUS_3_DB: f64 = std::f64::consts::FRAC_1_SQRT_2;
+        match self {
+            Channel::FrontLeft => Some((1.0, 0.0)),
+            Channel::FrontRight => Some((0.0, 1.0)),
+            Channel::FrontCenter | Channel::BackCenter => Some((MINUS_3_DB, MINUS_3_DB)),
+            Channel::Lfe => Some((0.0, 0.0)),
+            Channel::BackLeft | Channel::SideLeft => Some((MINUS_3_DB, 0.0)),
+            Channel::BackRight | Channel::SideRight => Some((0.0, MINUS_3_DB)),
+            Channel::Unknown => None,
+        }
+    }
+}
+
+// Default channel order of the source format, WAV and FLAC share the WAVEFORMATEXTENSIBLE
+// order while Vorbis puts the center channel between front left and right
+pub fn channel_layout(format: audrey::Format, channel_count: u32) -> Vec<Channel> {
+    use Channel::*;
+    let layout: &[Channel] = if format == audrey::Format::OggVorbis {
+        match channel_count {
+            1 => &[FrontCenter],
+            2 => &[FrontLeft, FrontRight],
+            3 => &[FrontLeft, FrontCenter, FrontRight],
+            4 => &[FrontLeft, FrontRight, BackLeft, BackRight],
+            5 => &[FrontLeft, FrontCenter, FrontRight, BackLeft, BackRight],
+            6 => &[FrontLeft, FrontCenter, FrontRight, BackLeft, BackRight, Lfe],
+            7 => &[
+                FrontLeft,
+                FrontCenter,
+                FrontRight,
+                SideLeft,
+                SideRight,
+                BackCenter,
+                Lfe,
+            ],
+            8 => &[
+                FrontLeft,
+                FrontCenter,
+                FrontRight,
+                SideLeft,
+                SideRight,
+                BackLeft,
+                BackRight,
+                Lfe,
+            ],
+            _ => &[],
+        }
+    } else {
+        match channel_count {
+            1 => &[FrontCenter],
+            2 => &[FrontLeft, FrontRight],
+            3 => &[FrontLeft, FrontRight, FrontCenter],
+            4 => &[FrontLeft, FrontRight, BackLeft, BackRight],
+            5 => &[FrontLeft, FrontRight, FrontCenter, BackLeft, BackRight],
+            6 => &[FrontLeft, FrontRight, FrontCenter, Lfe, BackLeft, BackRight],
+            7 => &[
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                Lfe,
+                BackCenter,
+                SideLeft,
+                SideRight,
+            ],
+            8 => &[
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                Lfe,
+                BackLeft,
+                BackRight,
+                SideLeft,
+                SideRight,
+            ],
+            _ => &[],
+        }
+    };
+
+    match layout.is_empty() {
+        true => vec![Unknown; channel_count as usize],
+        false => layout.to_vec(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MultichannelAudio<S = i16> {
+    // Interleaved, sample of channel c in frame i is at i * channel_count + c
+    pub samples: Vec<S>,
+    pub layout: Vec<Channel>,
+    pub sample_rate: u32,
+    pub format: audrey::Format,
+    pub sample_format: SampleFormat,
+}
+
+impl<S: DecoderSample> MultichannelAudio<S> {
+    pub fn channel_count(&self) -> usize {
+        self.layout.len()
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.samples.len() / self.channel_count()
+    }
+
+    pub fn frames(&self) -> std::slice::ChunksExact<'_, S> {
+        self.samples.chunks_exact(self.channel_count())
+    }
+
+    pub fn channel(&self, index: usize) -> Vec<S> {
+        self.frames().map(|frame| frame[index]).collect()
+    }
+
+    // One buffer per channel
+    pub fn to_planar(&self) -> Vec<Vec<S>> {
+        (0..self.channel_count()).map(|c| self.channel(c)).collect()
+    }
+
+    // Output is normalized by the total gain of each side so it can't clip
+    pub fn downmix_stereo(&self) -> Result<Vec<[S; 2]>, DecodeError> {
+        if let [channel] = self.layout[..] {
+            if channel != Channel::Unknown {
+                return Ok(self.samples.iter().map(|&s| [s, s]).collect());
+            }
+        }
+
+        let coefficients = self
+            .layout
+            .iter()
+            .map(Channel::stereo_coefficients)
+            .collect::<Option<Vec<(f64, f64)>>>()
+            .ok_or(DecodeError::UnsupportedChannelLayout(
+                self.channel_count() as u32
+            ))?;
+        let left_gain: f64 = coefficients.iter().map(|c| c.0).sum();
+        let right_gain: f64 = coefficients.iter().map(|c| c.1).sum();
+
+        let frames = self
+            .frames()
+            .map(|frame| {
+                let (left, right) = frame.iter().zip(&coefficients).fold(
+                    (0.0, 0.0),
+                    |(left, right), (&sample, (l, r))| {
+                        let sample: f64 = sample.to_sample_();
+                        (left + sample * l, right + sample * r)
+                    },
+                );
+                [
+                    S::from_sample_(left / left_gain),
+                    S::from_sample_(right / right_gain),
+                ]
+            })
+            .collect();
+
+        Ok(frames)
+    }
+
+    pub fn downmix_mono(&self) -> Result<Vec<S>, DecodeError> {
+        let frames = self.downmix_stereo()?;
+        Ok(frames
+            .iter()
+            .map(|&[left, right]| {
+                let (left, right): (f64, f64) = (left.to_sample_(), right.to_sample_());
+                S::from_sample_((left + right) / 2.0)
+            })
+            .collect())
+    }
+}
+
+pub(crate) fn open(path: &Path) -> Result<audrey::read::BufFileReader, DecodeError> {
+    audrey::open(path).map_err(|err| match err {
+        ReadError::Io(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            DecodeError::FileNotFound(path.to_path_buf())
+        }
+        ReadError::Io(err) => DecodeError::Io(err),
+        ReadError::Reader(err) => DecodeError::Read(err),
+        ReadError::UnsupportedFormat => DecodeError::UnsupportedFormat,
+    })
+}
+
+// audrey doesn't expose the bit depth in its description, so read it from the inner reader
+#[allow(unreachable_patterns)] // depends on the enabled audrey features
+fn sample_format(file: &audrey::read::BufFileReader) -> SampleFormat {
+    match file {
+        audrey::Reader::Wav(reader) => {
+            let spec = reader.spec();
+            match spec.sample_format {
+                audrey::hound::SampleFormat::Int => SampleFormat::Int(spec.bits_per_sample as u32),
+                audrey::hound::SampleFormat::Float => {
+                    SampleFormat::Float(spec.bits_per_sample as u32)
+                }
+            }
+        }
+        audrey::Reader::Flac(reader) => SampleFormat::Int(reader.streaminfo().bits_per_sample),
+        _ => SampleFormat::Unknown,
+    }
+}
+
+pub fn try_decode_multichannel<P>(path: P) -> Result<MultichannelAudio, DecodeError>
+where
+    P: AsRef<Path>,
+{
+    try_decode_multichannel_as(path)
+}
+
+pub fn try_decode_multichannel_as<S, P>(path: P) -> Result<MultichannelAudio<S>, DecodeError>
+where
+    S: DecoderSample,
+    P: AsRef<Path>,
+{
+    let mut file = open(path.as_ref())?;
+    read_multichannel(&mut file)
+}
+
+fn read_multichannel<S: DecoderSample>(
+    file: &mut audrey::read::BufFileReader,
+) -> Result<MultichannelAudio<S>, DecodeError> {
+    let description = file.description();
+    let channel_count = description.channel_count();
+    if channel_count == 0 {
+        return Err(DecodeError::UnsupportedChannelLayout(channel_count));
+    }
+
+    let sample_format = sample_format(file);
+    let mut samples = file.samples::<S>().collect::<Result<Vec<S>, _>>()?;
+    // Drop a trailing partial frame
+    samples.truncate(samples.len() - samples.len() % channel_count as usize);
+
+    Ok(MultichannelAudio {
+        samples,
+        layout: channel_layout(description.format(), channel_count),
+        sample_rate: description.sample_rate(),
+        format: description.format(),
+        sample_format,
+    })
+}
+
+// Sources with more than two channels are downmixed to stereo
+pub fn try_decode<P>(path: P) -> Result<DecodedAudio, DecodeError>
+where
+    P: AsRef<Path>,
+{
+    try_decode_as(path)
+}
+
+// Reads samples at the native precision of the source before converting them to S
+pub fn try_decode_as<S, P>(path: P) -> Result<DecodedAudio<S>, DecodeError>
+where
+    S: DecoderSample,
+    P: AsRef<Path>,
+{
+    let mut file = open(path.as_ref())?;
+    let description = file.description();
+    let sample_format = sample_format(&file);
+    let channel_count = description.channel_count();
+    let frames = match channel_count {
+        1 => file
+            .frames::<[S; 1]>()
+            .map(|f| f.map(|f| [f[0], f[0]]))
+            .collect::<Result<Vec<[S; 2]>, _>>()?,
+        2 => file
+            .frames::<[S; 2]>()
+            .collect::<Result<Vec<[S; 2]>, _>>()?,
+        _ => read_multichannel(&mut file)?.downmix_stereo()?,
+    };
+
+    Ok(DecodedAudio {
+        frames,
+        sample_rate: description.sample_rate(),
+        channel_count,
+        format: description.format(),
+        sample_format,
+    })
+}
+
+// Panics on any error, use try_decode to handle them
+pub fn decode<P>(path: P) -> DecodedAudio
 where
-    P: AsRef<std::path::Path>,
+    P: AsRef<Path>,
 {
     let file_path = path.as_ref().display().to_string();
-    let mut file =
-        audrey::open(path).expect(format!("Error while opening file: {}", file_path).as_ref());
-    println!("{} -> {:?}", file_path, file.description());
-    let channel_count = file.description().channel_count();
-    let frames: Vec<[i16; 2]>;
-    match channel_count {
-        1 => {
-            let mono_frames = file
-                .frames::<[i16; 1]>()
-                .map(Result::unwrap)
-                .collect::<Vec<[i16; 1]>>();
-
-            frames = mono_frames
-                .iter()
-                .map(|f| [f[0], f[0]])
-                .collect::<Vec<[i16; 2]>>();
-        }
-        _ => {
-            frames = file
-                .frames::<[i16; 2]>()
-                .map(Result::unwrap)
-                .collect::<Vec<[i16; 2]>>();
-        }
+    try_decode(path).unwrap_or_else(|err| panic!("Error while decoding {}: {}", file_path, err))
+}
+
+// Decodes and converts to sample_rate with the default windowed sinc resampler
+pub fn try_decode_with_sample_rate<P>(
+    path: P,
+    sample_rate: u32,
+) -> Result<DecodedAudio, DecodeError>
+where
+    P: AsRef<Path>,
+{
+    let audio = try_decode(path)?;
+    if audio.sample_rate == sample_rate {
+        return Ok(audio);
     }
 
-    frames
+    Ok(audio.resample(sample_rate, ResampleType::default()))
+}
+
+pub fn decode_with_sample_rate<P>(path: P, sample_rate: u32) -> DecodedAudio
+where
+    P: AsRef<Path>,
+{
+    let file_path = path.as_ref().display().to_string();
+    try_decode_with_sample_rate(path, sample_rate)
+        .unwrap_or_else(|err| panic!("Error while decoding {}: {}", file_path, err))
+}
+
+pub fn try_decode_f32<P>(path: P) -> Result<DecodedAudio<f32>, DecodeError>
+where
+    P: AsRef<Path>,
+{
+    try_decode_as(path)
 }
 
-// Under the assumption that sample rate is 44100 Hz
-pub fn duration_in_seconds<T>(frames: Vec<T>, sample_rate: f32) -> f32 {
-    frames.len() as f32 / sample_rate
+pub fn decode_f32<P>(path: P) -> DecodedAudio<f32>
+where
+    P: AsRef<Path>,
+{
+    let file_path = path.as_ref().display().to_string();
+    try_decode_f32(path).unwrap_or_else(|err| panic!("Error while decoding {}: {}", file_path, err))
 }
 
-pub fn detect_bpm(frames: Vec<[i16; 2]>, sample_rate: f32) -> u32 {
-    const C: f32 = 5.5;
-    let f_frames: Vec<[f32; 2]> = frames
-        .iter()
-        .map(|f| [f[0] as f32 / i16::MAX as f32, f[1] as f32 / i16::MAX as f32])
+pub fn try_decode_f64<P>(path: P) -> Result<DecodedAudio<f64>, DecodeError>
+where
+    P: AsRef<Path>,
+{
+    try_decode_as(path)
+}
+
+pub fn decode_f64<P>(path: P) -> DecodedAudio<f64>
+where
+    P: AsRef<Path>,
+{
+    let file_path = path.as_ref().display().to_string();
+    try_decode_f64(path).unwrap_or_else(|err| panic!("Error while decoding {}: {}", file_path, err))
+}
+
+pub fn duration_in_seconds<S>(audio: &DecodedAudio<S>) -> f32 {
+    audio.frames.len() as f32 / audio.sample_rate as f32
+}
+
+// Counts the hits, runs of blocks whose energy is well above the average energy of the second
+// around them. This is the tempo when every beat has one hit, busier rhythms read higher.
+// Returns None if the clip is shorter than half a second or no beats are found.
+pub fn detect_bpm<S: DecoderSample>(audio: &DecodedAudio<S>) -> Option<f32> {
+    const C: f64 = 5.5;
+    let sample_rate = audio.sample_rate as f64;
+    // 1024 samples at 44100 Hz, about 23 ms at any sample rate
+    let block_size = (sample_rate * 1024.0 / 44100.0).round() as usize;
+    if block_size == 0 {
+        return None;
+    }
+    let blocks_per_window = (sample_rate / block_size as f64).round() as usize;
+
+    let energies: Vec<f64> = audio
+        .frames
+        .chunks_exact(block_size)
+        .map(|block| {
+            block
+                .iter()
+                .map(|&[left, right]| {
+                    let (left, right): (f64, f64) = (left.to_sample_(), right.to_sample_());
+                    left * left + right * right
+                })
+                .sum()
+        })
         .collect();
+    if energies.is_empty() || energies.len() < blocks_per_window / 2 {
+        return None;
+    }
 
-    let duration_in_seconds = duration_in_seconds(frames, sample_rate) as usize;
     let mut beats = 0;
-    for i in 0..duration_in_seconds {
-        let start = i * 44100;
-        let end = (i + 1) * 44100;
-        let average_e: f32 = (start..end)
-            .map(|i| f_frames[i][0].powf(2.0) + f_frames[i][1].powf(2.0))
-            .sum();
-        let average_e = average_e * (1024.0 / 44100.0);
-        let frame_slice = &f_frames[start..end];
-
-        for j in 0..43 {
-            let (start, end) = (j * 1024, (j + 1) * 1024);
-            let e: f32 = (start..end)
-                .map(|i| frame_slice[i][0].powf(2.0) + frame_slice[i][1].powf(2.0))
-                .sum();
-
-            if e > C * average_e {
+    // A hit often spans more than one block, only the first block of a run counts
+    let mut was_above = false;
+    // The last window can be shorter than a second
+    for window in energies.chunks(blocks_per_window) {
+        let average_e = window.iter().sum::<f64>() / window.len() as f64;
+        for &e in window {
+            let above = e > C * average_e;
+            if above && !was_above {
                 beats += 1;
             }
+            was_above = above;
         }
     }
+    if beats == 0 {
+        return None;
+    }
 
-    // Beats is calculated for the duration of the sample, so extend it over a minute
-    beats * (60 / duration_in_seconds as u32)
+    // Beats is calculated for the analysed duration, so extend it over a minute
+    let duration_in_seconds = (energies.len() * block_size) as f32 / audio.sample_rate as f32;
+    Some(beats as f32 * 60.0 / duration_in_seconds)
 }
@@ -0,0 +1,277 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+// KSDATAFORMAT_SUBTYPE_PCM, 00000001-0000-0010-8000-00aa00389b71
+const SUBTYPE_PCM: [u8; 16] = [
+    0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+];
+// Size of the ds64 chunk without a table, the same amount is reserved with a JUNK chunk
+// so the header can be turned into RF64 in place once the data outgrows 2 GB
+const DS64_SIZE: u32 = 28;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WavFormat {
+    Pcm16,
+    Pcm24,
+    Float32,
+}
+
+impl WavFormat {
+    pub fn bits_per_sample(&self) -> u16 {
+        match self {
+            WavFormat::Pcm16 => 16,
+            WavFormat::Pcm24 => 24,
+            WavFormat::Float32 => 32,
+        }
+    }
+
+    fn bytes_per_sample(&self) -> u16 {
+        self.bits_per_sample() / 8
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WavSpec {
+    pub sample_rate: u32,
+    pub channel_count: u16,
+    pub format: WavFormat,
+}
+
+// Writes interleaved f32 samples in [-1, 1], integer formats clip samples outside that range.
+// The header is patched with the final sizes in finalize, or when the writer is dropped.
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    spec: WavSpec,
+    data_bytes: u64,
+    finalized: bool,
+}
+
+impl WavWriter<BufWriter<File>> {
+    pub fn create<P>(path: P, spec: WavSpec) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        WavWriter::new(BufWriter::new(File::create(path)?), spec)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut writer: W, spec: WavSpec) -> io::Result<Self> {
+        if !(1..=2).contains(&spec.channel_count) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unsupported channel count: {}", spec.channel_count),
+            ));
+        }
+        writer.write_all(&header(&spec, 0))?;
+
+        Ok(Self {
+            writer,
+            spec,
+            data_bytes: 0,
+            finalized: false,
+        })
+    }
+
+    pub fn spec(&self) -> WavSpec {
+        self.spec
+    }
+
+    // Number of frames written so far
+    pub fn frame_count(&self) -> u64 {
+        self.data_bytes / block_align(&self.spec) as u64
+    }
+
+    pub fn write_sample(&mut self, sample: f32) -> io::Result<()> {
+        match self.spec.format {
+            // Same scaling as the decoder, so a decoded file can be written back unchanged
+            WavFormat::Pcm16 => {
+                let value = (sample as f64 * 32_768.0)
+                    .round()
+                    .clamp(-32_768.0, 32_767.0);
+                self.writer.write_all(&(value as i16).to_le_bytes())?;
+            }
+            WavFormat::Pcm24 => {
+                let value = (sample as f64 * 8_388_608.0)
+                    .round()
+                    .clamp(-8_388_608.0, 8_388_607.0);
+                self.writer.write_all(&(value as i32).to_le_bytes()[..3])?;
+            }
+            WavFormat::Float32 => self.writer.write_all(&sample.to_le_bytes())?,
+        }
+        self.data_bytes += self.spec.format.bytes_per_sample() as u64;
+
+        Ok(())
+    }
+
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        samples.iter().try_for_each(|&s| self.write_sample(s))
+    }
+
+    pub fn write_frames(&mut self, frames: &[[f32; 2]]) -> io::Result<()> {
+        if self.spec.channel_count != 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Stereo frames need a writer with 2 channels",
+            ));
+        }
+        frames
+            .iter()
+            .try_for_each(|frame| self.write_samples(frame))
+    }
+
+    pub fn finalize(mut self) -> io::Result<()> {
+        self.finalize_inner()
+    }
+
+    fn finalize_inner(&mut self) -> io::Result<()> {
+        if self.finalized {
+            return Ok(());
+        }
+        self.finalized = true;
+
+        // Chunks have to be word aligned
+        if self.data_bytes % 2 == 1 {
+            self.writer.write_all(&[0])?;
+        }
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer
+            .write_all(&header(&self.spec, self.data_bytes))?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()
+    }
+}
+
+impl<W: Write + Seek> Drop for WavWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.finalize_inner();
+    }
+}
+
+fn block_align(spec: &WavSpec) -> u16 {
+    spec.channel_count * spec.format.bytes_per_sample()
+}
+
+// Builds the header for data_bytes of sample data, RF64 is used when the sizes don't fit in i32
+pub(crate) fn header(spec: &WavSpec, data_bytes: u64) -> Vec<u8> {
+    let is_float = spec.format == WavFormat::Float32;
+    // Integer samples over 16 bits should be described with WAVE_FORMAT_EXTENSIBLE
+    let is_extensible = spec.format == WavFormat::Pcm24;
+    let fmt_size: u32 = match (is_float, is_extensible) {
+        (true, _) => 18,
+        (_, true) => 40,
+        _ => 16,
+    };
+    // Float data has to come with a fact chunk that holds the frame count
+    let fact_size: u32 = if is_float { 12 } else { 0 };
+    let padded_data_bytes = data_bytes + data_bytes % 2;
+    let riff_size = 4
+        + (8 + DS64_SIZE as u64)
+        + (8 + fmt_size as u64)
+        + fact_size as u64
+        + 8
+        + padded_data_bytes;
+    let frame_count = data_bytes / block_align(spec) as u64;
+    // Many readers take the sizes as signed, hound among them, so anything over 2 GB is RF64
+    let is_rf64 = riff_size > i32::MAX as u64;
+
+    let mut header = Vec::with_capacity(100);
+    if is_rf64 {
+        header.extend_from_slice(b"RF64");
+        header.extend_from_slice(&u32::MAX.to_le_bytes());
+        header.extend_from_slice(b"WAVE");
+        header.extend_from_slice(b"ds64");
+        header.extend_from_slice(&DS64_SIZE.to_le_bytes());
+        header.extend_from_slice(&riff_size.to_le_bytes());
+        header.extend_from_slice(&data_bytes.to_le_bytes());
+        header.extend_from_slice(&frame_count.to_le_bytes());
+        header.extend_from_slice(&0_u32.to_le_bytes()); // table length
+    } else {
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(riff_size as u32).to_le_bytes());
+        header.extend_from_slice(b"WAVE");
+        header.extend_from_slice(b"JUNK");
+        header.extend_from_slice(&DS64_SIZE.to_le_bytes());
+        header.extend_from_slice(&[0; DS64_SIZE as usize]);
+    }
+
+    let format_tag = match (is_float, is_extensible) {
+        (true, _) => WAVE_FORMAT_IEEE_FLOAT,
+        (_, true) => WAVE_FORMAT_EXTENSIBLE,
+        _ => WAVE_FORMAT_PCM,
+    };
+    let block_align = block_align(spec);
+    header.extend_from_slice(b"fmt ");
+    header.extend_from_slice(&fmt_size.to_le_bytes());
+    header.extend_from_slice(&format_tag.to_le_bytes());
+    header.extend_from_slice(&spec.channel_count.to_le_bytes());
+    header.extend_from_slice(&spec.sample_rate.to_le_bytes());
+    header.extend_from_slice(&(spec.sample_rate * block_align as u32).to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&spec.format.bits_per_sample().to_le_bytes());
+    if is_float {
+        header.extend_from_slice(&0_u16.to_le_bytes()); // extension size
+        header.extend_from_slice(b"fact");
+        header.extend_from_slice(&4_u32.to_le_bytes());
+        header.extend_from_slice(&(frame_count.min(u32::MAX as u64) as u32).to_le_bytes());
+    }
+    if is_extensible {
+        let channel_mask: u32 = match spec.channel_count {
+            1 => 0x4, // front center
+            2 => 0x3, // front left and right
+            _ => 0,
+        };
+        header.extend_from_slice(&22_u16.to_le_bytes()); // extension size
+        header.extend_from_slice(&spec.format.bits_per_sample().to_le_bytes()); // valid bits
+        header.extend_from_slice(&channel_mask.to_le_bytes());
+        header.extend_from_slice(&SUBTYPE_PCM);
+    }
+
+    header.extend_from_slice(b"data");
+    let data_size = if is_rf64 { u32::MAX } else { data_bytes as u32 };
+    header.extend_from_slice(&data_size.to_le_bytes());
+
+    header
+}
+
+pub fn write_mono<P>(
+    path: P,
+    samples: &[f32],
+    sample_rate: u32,
+    format: WavFormat,
+) -> io::Result<()>
+where
+    P: AsRef<Path>,
+{
+    let spec = WavSpec {
+        sample_rate,
+        channel_count: 1,
+        format,
+    };
+    let mut writer = WavWriter::create(path, spec)?;
+    writer.write_samples(samples)?;
+    writer.finalize()
+}
+
+pub fn write_stereo<P>(
+    path: P,
+    frames: &[[f32; 2]],
+    sample_rate: u32,
+    format: WavFormat,
+) -> io::Result<()>
+where
+    P: AsRef<Path>,
+{
+    let spec = WavSpec {
+        sample_rate,
+        channel_count: 2,
+        format,
+    };
+    let mut writer = WavWriter::create(path, spec)?;
+    writer.write_frames(frames)?;
+    writer.finalize()
+}
@@ -7,7 +7,10 @@ const RELEASE_RANGE: RangeInclusive<f32> = 0.8..=0.0;
 pub struct Envelope {
     state: EnvelopeState,
     volume: f32,
+    // Volume when the release started, the release fades from here to silence
+    release_level: f32,
     tick: f32,
+    samples_per_second: f32,
     attack_duration: f32,
     decay_duration: f32,
     release_duration: f32,
@@ -19,25 +22,35 @@ impl Envelope {
         Self {
             state: EnvelopeState::Idle,
             volume: 0.0,
+            release_level: 0.0,
             tick: 0.0,
+            samples_per_second,
             attack_duration: 1.0 * samples_per_second,
             decay_duration: 1.0 * samples_per_second,
             release_duration: 2.0 * samples_per_second,
         }
     }
 
+    // Durations are in seconds, at least one sample long
+    pub fn set_attack(&mut self, seconds: f32) {
+        self.attack_duration = (seconds * self.samples_per_second).max(1.0);
+    }
+
+    pub fn set_decay(&mut self, seconds: f32) {
+        self.decay_duration = (seconds * self.samples_per_second).max(1.0);
+    }
+
+    pub fn set_release(&mut self, seconds: f32) {
+        self.release_duration = (seconds * self.samples_per_second).max(1.0);
+    }
+
     pub fn press(&mut self) {
         match self.state {
             EnvelopeState::Idle => {
                 self.tick = 0.0;
             }
             EnvelopeState::Attack => (),
-            EnvelopeState::Decay => {
-                self.tick = reverse_volume(*ATTACK_RANGE.start(), *ATTACK_RANGE.end(), self.volume)
-                    * self.attack_duration;
-            }
-            EnvelopeState::Sustain => (),
-            EnvelopeState::Release => {
+            EnvelopeState::Decay | EnvelopeState::Sustain | EnvelopeState::Release => {
                 self.tick = reverse_volume(*ATTACK_RANGE.start(), *ATTACK_RANGE.end(), self.volume)
                     * self.attack_duration;
             }
@@ -46,7 +59,16 @@ impl Envelope {
     }
 
     pub fn release(&mut self) {
-        // self.state = EnvelopeState::Release;
+        match self.state {
+            EnvelopeState::Idle | EnvelopeState::Release => (),
+            _ => {
+                // Continues from the current volume, so releasing early still takes the
+                // whole release duration
+                self.release_level = self.volume;
+                self.tick = 0.0;
+                self.state = EnvelopeState::Release;
+            }
+        }
     }
 
     // Envelope time should be based on hardware tick, not the cpu timer
@@ -75,21 +97,24 @@ impl Envelope {
                 );
                 if self.tick >= self.decay_duration {
                     self.tick = 0.0;
-                    self.state = EnvelopeState::Release;
+                    self.state = EnvelopeState::Sustain;
                 }
             }
+            // Holds the end of the decay until release
             EnvelopeState::Sustain => {
-                print!("Sustain");
+                self.volume = *DECAY_RANGE.end();
             }
             EnvelopeState::Release => {
                 // println!("Release");
                 self.volume = lerp(
-                    *RELEASE_RANGE.start(),
+                    self.release_level,
                     *RELEASE_RANGE.end(),
                     self.tick / self.release_duration,
                 );
                 self.tick += 1.0;
+                // Ends on silence, not a step above it
                 if self.tick >= self.release_duration {
+                    self.volume = *RELEASE_RANGE.end();
                     self.state = EnvelopeState::Idle;
                 }
             }
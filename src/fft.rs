@@ -1,49 +1,460 @@
 use num::complex::Complex;
+use num::traits::{Float, NumAssign};
+use num::Zero;
 use std::f64::consts::PI;
+use std::fmt::Debug;
 
-const I: Complex<f64> = Complex { re: 0.0, im: 1.0 };
-
-pub fn fft(input: &[Complex<f64>]) -> Vec<Complex<f64>> {
-    fn fft_inner(
-        buf_a: &mut [Complex<f64>],
-        buf_b: &mut [Complex<f64>],
-        n: usize, // Total lenght of the input array
-        step: usize,
-    ) // Precalculated values for t
-    {
-        if step >= n {
+// Sample types the FFT works on, f32 and f64. Tables are computed in f64 either way
+pub trait FftFloat: Float + NumAssign + Debug {}
+
+impl<T: Float + NumAssign + Debug> FftFloat for T {}
+
+// Sizes made of these factors use the mixed radix path, others fall back to Bluestein
+const RADICES: [usize; 3] = [5, 3, 2];
+
+#[derive(Debug, Clone)]
+enum Algorithm<T: FftFloat> {
+    // Powers of two, transformed in place without scratch space
+    Radix2,
+    // Sizes of the form 2^a * 3^b * 5^c, the factors in the order they are applied
+    MixedRadix(Vec<usize>),
+    // Any other size, computed as a convolution with a chirp through a power of two plan
+    Bluestein {
+        inner: Box<FftPlan<T>>,
+        // e^(-iπk²/n) for k in 0..n
+        chirp: Vec<Complex<T>>,
+        // Spectrum of the conjugated chirp, already scaled for the inverse inner transform
+        kernel: Vec<Complex<T>>,
+    },
+}
+
+// Precomputed tables for transforms of one size. Power of two sizes are transformed in place
+// without allocating, so a plan can be made once and used in an audio callback. Other sizes
+// need scratch space, pass it to the _with_scratch methods to keep them allocation free.
+#[derive(Debug, Clone)]
+pub struct FftPlan<T: FftFloat = f64> {
+    size: usize,
+    // e^(-2πik/n) for k in 0..n
+    twiddles: Vec<Complex<T>>,
+    // Index pairs that trade places in the bit reversal permutation
+    swaps: Vec<(usize, usize)>,
+    algorithm: Algorithm<T>,
+}
+
+impl<T: FftFloat> FftPlan<T> {
+    pub fn new(size: usize) -> Self {
+        let twiddles = (0..size)
+            .map(|k| cis(-2.0 * PI * k as f64 / size as f64))
+            .collect();
+        let mut swaps = vec![];
+        let algorithm = if size <= 1 || size.is_power_of_two() {
+            let shift = usize::BITS - size.trailing_zeros();
+            swaps = (0..size)
+                .map(|i| (i, i.reverse_bits().checked_shr(shift).unwrap_or(0)))
+                .filter(|(i, j)| i < j)
+                .collect();
+            Algorithm::Radix2
+        } else if let Some(factors) = factorize(size) {
+            Algorithm::MixedRadix(factors)
+        } else {
+            // A linear convolution of two size long sequences fits in 2 * size - 1
+            let inner_size = (2 * size - 1).next_power_of_two();
+            let inner = FftPlan::new(inner_size);
+            let chirp: Vec<Complex<T>> = (0..size)
+                .map(|k| {
+                    // k² modulo 2n keeps the angle small and precise for large k
+                    let k_squared = (k as u128 * k as u128 % (2 * size as u128)) as f64;
+                    cis(-PI * k_squared / size as f64)
+                })
+                .collect();
+            let mut kernel = vec![Complex::zero(); inner_size];
+            kernel[0] = chirp[0].conj();
+            for k in 1..size {
+                kernel[k] = chirp[k].conj();
+                kernel[inner_size - k] = chirp[k].conj();
+            }
+            inner.forward(&mut kernel);
+            let scale = real::<T>(1.0 / inner_size as f64);
+            kernel.iter_mut().for_each(|c| *c = c.scale(scale));
+            Algorithm::Bluestein {
+                inner: Box::new(inner),
+                chirp,
+                kernel,
+            }
+        };
+
+        Self {
+            size,
+            twiddles,
+            swaps,
+            algorithm,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // Length of the scratch buffer the _with_scratch methods need, 0 for powers of two
+    pub fn scratch_len(&self) -> usize {
+        match &self.algorithm {
+            Algorithm::Radix2 => 0,
+            Algorithm::MixedRadix(_) => self.size,
+            Algorithm::Bluestein { inner, .. } => inner.size,
+        }
+    }
+
+    // Same result as fft, buffer has to be exactly size long. Allocates scratch space when
+    // size is not a power of two
+    pub fn forward(&self, buffer: &mut [Complex<T>]) {
+        let mut scratch = vec![Complex::zero(); self.scratch_len()];
+        self.forward_with_scratch(buffer, &mut scratch);
+    }
+
+    // Same result as ifft, scaled by 1 / size
+    pub fn inverse(&self, buffer: &mut [Complex<T>]) {
+        let mut scratch = vec![Complex::zero(); self.scratch_len()];
+        self.inverse_with_scratch(buffer, &mut scratch);
+    }
+
+    // scratch has to be at least scratch_len long, its contents are overwritten
+    pub fn forward_with_scratch(&self, buffer: &mut [Complex<T>], scratch: &mut [Complex<T>]) {
+        assert_eq!(self.size, buffer.len(), "buffer length must match the plan");
+        assert!(
+            scratch.len() >= self.scratch_len(),
+            "scratch must be at least scratch_len long"
+        );
+        match &self.algorithm {
+            Algorithm::Radix2 => self.radix2(buffer),
+            Algorithm::MixedRadix(factors) => {
+                let input = &mut scratch[..self.size];
+                input.copy_from_slice(buffer);
+                self.mixed_radix(input, 1, buffer, factors, 1);
+            }
+            Algorithm::Bluestein {
+                inner,
+                chirp,
+                kernel,
+            } => {
+                let convolution = &mut scratch[..inner.size];
+                convolution.fill(Complex::zero());
+                for ((c, x), w) in convolution.iter_mut().zip(buffer.iter()).zip(chirp) {
+                    *c = x * w;
+                }
+                inner.forward(convolution);
+                convolution
+                    .iter_mut()
+                    .zip(kernel)
+                    .for_each(|(c, k)| *c *= k);
+                // Inverse by conjugation, the 1 / n scale is already part of kernel
+                convolution.iter_mut().for_each(|c| *c = c.conj());
+                inner.forward(convolution);
+                for ((x, c), w) in buffer.iter_mut().zip(convolution.iter()).zip(chirp) {
+                    *x = c.conj() * w;
+                }
+            }
+        }
+    }
+
+    pub fn inverse_with_scratch(&self, buffer: &mut [Complex<T>], scratch: &mut [Complex<T>]) {
+        // Conjugating before and after the forward transform reverses its direction
+        buffer.iter_mut().for_each(|c| *c = c.conj());
+        self.forward_with_scratch(buffer, scratch);
+        let scale = real::<T>(1.0 / self.size as f64);
+        buffer.iter_mut().for_each(|c| *c = c.conj().scale(scale));
+    }
+
+    fn radix2(&self, buffer: &mut [Complex<T>]) {
+        for &(i, j) in &self.swaps {
+            buffer.swap(i, j);
+        }
+
+        // Iterative radix-2 butterflies, merging transforms of length / 2 into length
+        let mut length = 2;
+        while length <= self.size {
+            let stride = self.size / length;
+            for chunk in buffer.chunks_exact_mut(length) {
+                let (left, right) = chunk.split_at_mut(length / 2);
+                for (k, (a, b)) in left.iter_mut().zip(right.iter_mut()).enumerate() {
+                    let t = self.twiddles[k * stride] * *b;
+                    *b = *a - t;
+                    *a += t;
+                }
+            }
+            length *= 2;
+        }
+    }
+
+    // Decimation in time: output is the transform of every stride-th input sample. It is
+    // split into factors[0] interleaved transforms that are combined with twiddles
+    fn mixed_radix(
+        &self,
+        input: &[Complex<T>],
+        stride: usize,
+        output: &mut [Complex<T>],
+        factors: &[usize],
+        twiddle_stride: usize,
+    ) {
+        let length = output.len();
+        let Some((&radix, factors)) = factors.split_first() else {
+            output[0] = input[0];
             return;
+        };
+        let sub_length = length / radix;
+        for (r, sub_output) in output.chunks_exact_mut(sub_length).enumerate() {
+            self.mixed_radix(
+                &input[r * stride..],
+                stride * radix,
+                sub_output,
+                factors,
+                twiddle_stride * radix,
+            );
         }
 
-        fft_inner(buf_b, buf_a, n, step * 2);
-        fft_inner(&mut buf_b[step..], &mut buf_a[step..], n, step * 2);
-        // Create a slice for each half of buf_a:
-        let (left, right) = buf_a.split_at_mut(n / 2);
+        // Roots of unity of the radix point DFT itself
+        let mut roots = [Complex::zero(); RADICES[0]];
+        for (j, root) in roots[..radix].iter_mut().enumerate() {
+            *root = self.twiddles[j * self.size / radix];
+        }
+        let mut values = [Complex::zero(); RADICES[0]];
+        for k in 0..sub_length {
+            for (r, value) in values[..radix].iter_mut().enumerate() {
+                *value = output[r * sub_length + k] * self.twiddles[r * k * twiddle_stride];
+            }
+            for s in 0..radix {
+                let mut sum = values[0];
+                let mut j = 0;
+                for value in &values[1..radix] {
+                    j = (j + s) % radix;
+                    sum += value * roots[j];
+                }
+                output[s * sub_length + k] = sum;
+            }
+        }
+    }
+}
+
+// e^(i angle), computed in f64 so f32 tables are as precise as they can be
+fn cis<T: FftFloat>(angle: f64) -> Complex<T> {
+    let (sin, cos) = angle.sin_cos();
+    Complex::new(real(cos), real(sin))
+}
+
+fn convert<T: FftFloat>(value: Complex<f64>) -> Complex<T> {
+    Complex::new(real(value.re), real(value.im))
+}
+
+fn real<T: FftFloat>(value: f64) -> T {
+    T::from(value).unwrap_or_else(T::nan)
+}
 
-        for i in (0..n).step_by(step * 2) {
-            let t = (-I * PI * (i as f64) / (n as f64)).exp() * buf_b[i + step];
-            left[i / 2] = buf_b[i] + t;
-            right[i / 2] = buf_b[i] - t;
+// Factors of size if they are all in RADICES
+fn factorize(mut size: usize) -> Option<Vec<usize>> {
+    let mut factors = vec![];
+    for radix in RADICES {
+        while size.is_multiple_of(radix) {
+            factors.push(radix);
+            size /= radix;
         }
     }
+    (size == 1).then_some(factors)
+}
+
+// Pads input with zeros up to the next power of two. Padding interpolates the spectrum, the
+// bins get closer together but their resolution doesn't improve
+pub fn pad_to_power_of_two<T: Clone + Zero>(input: &[T]) -> Vec<T> {
+    let mut padded = input.to_vec();
+    padded.resize(input.len().next_power_of_two(), T::zero());
+    padded
+}
+
+// Exact transform of any length, use pad_to_power_of_two first for the fastest sizes.
+// Makes a new plan on every call, keep an FftPlan around when transforming many blocks
+pub fn fft<T: FftFloat>(input: &[Complex<T>]) -> Vec<Complex<T>> {
+    let mut buffer = input.to_vec();
+    FftPlan::new(buffer.len()).forward(&mut buffer);
+    buffer
+}
+
+// Inverse of fft, scaled by 1 / n so that ifft(fft(x)) gives back x
+pub fn ifft<T: FftFloat>(input: &[Complex<T>]) -> Vec<Complex<T>> {
+    let mut buffer = input.to_vec();
+    FftPlan::new(buffer.len()).inverse(&mut buffer);
+    buffer
+}
+
+// Precomputed tables for real transforms of one size, see rfft. Even sizes run a complex plan
+// of half the size, powers of two need no scratch space and don't allocate.
+#[derive(Debug, Clone)]
+pub struct RfftPlan<T: FftFloat = f64> {
+    size: usize,
+    // Half the size for even sizes, the full size for odd ones
+    inner: FftPlan<T>,
+    // e^(-iπk/half) for k in 0..=half, empty for odd sizes
+    twiddles: Vec<Complex<T>>,
+}
+
+impl<T: FftFloat> RfftPlan<T> {
+    pub fn new(size: usize) -> Self {
+        let half = size / 2;
+        let (inner, twiddles) = match size % 2 == 1 {
+            true => (FftPlan::new(size), vec![]),
+            false => (
+                FftPlan::new(half),
+                (0..=half)
+                    .map(|k| cis(-PI * k as f64 / half.max(1) as f64))
+                    .collect(),
+            ),
+        };
+
+        Self {
+            size,
+            inner,
+            twiddles,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // Bins in the output, size / 2 + 1
+    pub fn spectrum_len(&self) -> usize {
+        self.size / 2 + 1
+    }
+
+    // Length of the scratch buffer forward_with_scratch needs, 0 for powers of two
+    pub fn scratch_len(&self) -> usize {
+        match self.size % 2 == 1 {
+            true => self.size + self.inner.scratch_len(),
+            false => self.inner.scratch_len(),
+        }
+    }
+
+    // Same result as rfft, output has to be spectrum_len long. Allocates scratch space when
+    // size is not a power of two
+    pub fn forward(&self, input: &[T], output: &mut [Complex<T>]) {
+        let mut scratch = vec![Complex::zero(); self.scratch_len()];
+        self.forward_with_scratch(input, output, &mut scratch);
+    }
+
+    // scratch has to be at least scratch_len long, its contents are overwritten
+    pub fn forward_with_scratch(
+        &self,
+        input: &[T],
+        output: &mut [Complex<T>],
+        scratch: &mut [Complex<T>],
+    ) {
+        assert_eq!(self.size, input.len(), "input length must match the plan");
+        assert_eq!(
+            self.spectrum_len(),
+            output.len(),
+            "output must be spectrum_len long"
+        );
+        assert!(
+            scratch.len() >= self.scratch_len(),
+            "scratch must be at least scratch_len long"
+        );
+        let n = self.size;
+        if n % 2 == 1 {
+            // The packing below needs pairs of samples
+            let (buffer, scratch) = scratch.split_at_mut(n);
+            for (value, &x) in buffer.iter_mut().zip(input) {
+                *value = Complex::new(x, T::zero());
+            }
+            self.inner.forward_with_scratch(buffer, scratch);
+            output.copy_from_slice(&buffer[..output.len()]);
+            return;
+        }
+        if n == 0 {
+            output[0] = Complex::zero();
+            return;
+        }
+
+        // Pack even samples into the real and odd samples into the imaginary part, so a
+        // half-length complex fft does the work
+        let half = n / 2;
+        for (value, pair) in output.iter_mut().zip(input.chunks_exact(2)) {
+            *value = Complex::new(pair[0], pair[1]);
+        }
+        self.inner
+            .forward_with_scratch(&mut output[..half], scratch);
+
+        let half_value = real::<T>(0.5);
+        let bin = |a: Complex<T>, b: Complex<T>, twiddle: Complex<T>| {
+            let even = (a + b).scale(half_value);
+            // (a - b) / 2i
+            let odd = Complex::new(a.im - b.im, b.re - a.re).scale(half_value);
+            even + twiddle * odd
+        };
+        // Bins k and half - k are made from the same two values, so they are split in place
+        // in pairs
+        for k in 0..=half / 2 {
+            let j = half - k;
+            let (a, b) = (output[k % half], output[j % half]);
+            output[k] = bin(a, b.conj(), self.twiddles[k]);
+            output[j] = bin(b, a.conj(), self.twiddles[j]);
+        }
+    }
+}
+
+// FFT of a real signal. The second half of a real spectrum mirrors the first, so only
+// the n / 2 + 1 bins from 0 Hz to Nyquist are returned.
+// Makes a new plan on every call, keep an RfftPlan around when transforming many blocks
+pub fn rfft<T: FftFloat>(input: &[T]) -> Vec<Complex<T>> {
+    let plan = RfftPlan::new(input.len());
+    let mut spectrum = vec![Complex::zero(); plan.spectrum_len()];
+    plan.forward(input, &mut spectrum);
+    spectrum
+}
+
+// Inverse of rfft, takes the n / 2 + 1 bins of a real spectrum and returns the n samples.
+// Both n = 2k and n = 2k + 1 give k + 1 bins, so n has to be passed in
+pub fn irfft<T: FftFloat>(spectrum: &[Complex<T>], n: usize) -> Vec<T> {
+    assert_eq!(
+        n / 2 + 1,
+        spectrum.len(),
+        "a real spectrum of {} samples has n / 2 + 1 bins",
+        n
+    );
+    if n % 2 == 1 {
+        // Rebuild the mirrored half and take the complex path
+        let full: Vec<Complex<T>> = (0..n)
+            .map(|k| match k < spectrum.len() {
+                true => spectrum[k],
+                false => spectrum[n - k].conj(),
+            })
+            .collect();
+        return ifft(&full).iter().map(|c| c.re).collect();
+    }
+    if n == 0 {
+        return vec![];
+    }
+
+    // Undo the split of rfft to get the spectrum of the packed even and odd samples
+    let half = n / 2;
+    let rotation = cis::<f64>(PI / half as f64);
+    let mut twiddle = Complex::new(1.0, 0.0);
+    let half_value = real::<T>(0.5);
+    let packed: Vec<Complex<T>> = (0..half)
+        .map(|k| {
+            let a = spectrum[k];
+            let b = spectrum[half - k].conj();
+            let even = (a + b).scale(half_value);
+            let odd = (a - b).scale(half_value) * convert::<T>(twiddle);
+            twiddle *= rotation;
+            // even + i * odd
+            Complex::new(even.re - odd.im, even.im + odd.re)
+        })
+        .collect();
 
-    // Round n (length) up to a power of 2:
-    let n_orig = input.len();
-    let n = n_orig.next_power_of_two();
-    // Copy the input into a buffer:
-    let mut buf_a = input.to_vec();
-    // Right pad with zeros to a power of two:
-    buf_a.append(&mut vec![Complex { re: 0.0, im: 0.0 }; n - n_orig]);
-    // Alternate between buf_a an buf_b to avoid allocating a new vector each time:
-    let mut buf_b = buf_a.clone();
-    fft_inner(&mut buf_a, &mut buf_b, n, 1);
-    buf_a
+    ifft(&packed).iter().flat_map(|c| [c.re, c.im]).collect()
 }
 
-pub fn show(label: &str, buf: &[Complex<f64>]) {
+pub fn show<T: std::fmt::Display>(label: &str, buf: &[Complex<T>]) {
     println!("{}", label);
     let string = buf
-        .into_iter()
+        .iter()
         .map(|x| format!("{:.4}{:+.4}i", x.re, x.im))
         .collect::<Vec<_>>()
         .join(", ");
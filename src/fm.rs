@@ -0,0 +1,213 @@
+use crate::envelope::{Envelope, EnvelopeState};
+use crate::oscillator::Oscillator;
+use std::f32::consts::PI;
+
+// A sine oscillator with its own envelope, the building block of a DX-style FM voice.
+// Operators modulate the phase of others, so level is the peak modulation index in radians
+// for a modulator and the amplitude for a carrier
+pub struct Operator {
+    // Frequency as a multiple of the note, plus a fixed detune in Hz
+    pub ratio: f32,
+    pub detune: f32,
+    pub level: f32,
+    // Modulation index in radians of the operator's own output fed back into its phase
+    pub feedback: f32,
+    pub envelope: Envelope,
+    oscillator: Oscillator,
+    // The last two outputs, feedback uses their average to keep it from oscillating
+    history: [f32; 2],
+}
+
+impl Operator {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut envelope = Envelope::new(sample_rate, 1);
+        envelope.set_attack(0.005);
+        envelope.set_decay(0.5);
+        envelope.set_release(1.0);
+        Self {
+            ratio: 1.0,
+            detune: 0.0,
+            level: 1.0,
+            feedback: 0.0,
+            envelope,
+            oscillator: Oscillator::new(sample_rate),
+            history: [0.0; 2],
+        }
+    }
+
+    // Next output for a note at frequency, with modulation the summed output of the
+    // operators modulating this one
+    pub fn run(&mut self, frequency: f32, modulation: f32) -> f32 {
+        let feedback = self.feedback * (self.history[0] + self.history[1]) / 2.0;
+        // Phase offsets are in cycles, modulation is in radians
+        let phase_offset = (modulation + feedback) / (2.0 * PI);
+        let value = self
+            .oscillator
+            .run_modulated(frequency * self.ratio + self.detune, phase_offset);
+        let output = self.level * self.envelope.update() * value;
+        self.history = [output, self.history[0]];
+
+        output
+    }
+
+    pub fn trigger(&mut self) {
+        self.oscillator.reset();
+        self.history = [0.0; 2];
+        self.envelope.press();
+    }
+}
+
+// Routing of the operators. Operators can only modulate operators with a lower index, so
+// every modulator is run before the operators it modulates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Algorithm {
+    // Operators whose outputs are mixed into the voice's output
+    pub carriers: Vec<usize>,
+    // (modulator, modulated) pairs
+    pub modulations: Vec<(usize, usize)>,
+}
+
+impl Algorithm {
+    pub fn new(carriers: Vec<usize>, modulations: Vec<(usize, usize)>) -> Self {
+        assert!(
+            modulations.iter().all(|&(from, to)| from > to),
+            "operators can only modulate operators with a lower index"
+        );
+        Self {
+            carriers,
+            modulations,
+        }
+    }
+
+    // Every operator modulates the one before it, operator 0 is the only carrier.
+    // Brassy and bell-like tones, brighter the more operators
+    pub fn stack(operator_count: usize) -> Self {
+        Self::new(vec![0], (1..operator_count).map(|i| (i, i - 1)).collect())
+    }
+
+    // Carrier and modulator pairs, 1 modulating 0, 3 modulating 2 and so on. The classic
+    // electric piano layout, one pair for the tone and one for the tine
+    pub fn pairs(operator_count: usize) -> Self {
+        Self::new(
+            (0..operator_count).step_by(2).collect(),
+            (1..operator_count).step_by(2).map(|i| (i, i - 1)).collect(),
+        )
+    }
+
+    // All operators are carriers, additive synthesis of up to as many sines
+    pub fn parallel(operator_count: usize) -> Self {
+        Self::new((0..operator_count).collect(), vec![])
+    }
+
+    // Operators needed, one more than the highest index used
+    pub fn operator_count(&self) -> usize {
+        self.carriers
+            .iter()
+            .chain(self.modulations.iter().map(|(from, _)| from))
+            .max()
+            .map_or(0, |&i| i + 1)
+    }
+}
+
+// A monophonic voice of several operators, like one voice of a DX7
+pub struct FmVoice {
+    // Private so the count can't drop below what the algorithm indexes
+    operators: Vec<Operator>,
+    sample_rate: f32,
+    algorithm: Algorithm,
+    frequency: f32,
+    // Summed modulation of every operator for the current sample
+    modulation: Vec<f32>,
+}
+
+impl FmVoice {
+    pub fn new(sample_rate: f32, algorithm: Algorithm) -> Self {
+        let operator_count = algorithm.operator_count();
+        Self {
+            operators: (0..operator_count)
+                .map(|_| Operator::new(sample_rate))
+                .collect(),
+            sample_rate,
+            modulation: vec![0.0; operator_count],
+            algorithm,
+            frequency: 0.0,
+        }
+    }
+
+    pub fn operators(&self) -> &[Operator] {
+        &self.operators
+    }
+
+    // A slice, operators are only added or removed through set_algorithm
+    pub fn operators_mut(&mut self) -> &mut [Operator] {
+        &mut self.operators
+    }
+
+    pub fn operator_mut(&mut self, index: usize) -> &mut Operator {
+        &mut self.operators[index]
+    }
+
+    pub fn algorithm(&self) -> &Algorithm {
+        &self.algorithm
+    }
+
+    // Operators are added when the algorithm uses more than there are
+    pub fn set_algorithm(&mut self, algorithm: Algorithm) {
+        while self.operators.len() < algorithm.operator_count() {
+            self.operators.push(Operator::new(self.sample_rate));
+        }
+        self.modulation = vec![0.0; self.operators.len()];
+        self.algorithm = algorithm;
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    // Changes the pitch without retriggering, for glides
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.frequency = frequency;
+    }
+
+    // Starts a note, restarting every operator's phase and envelope
+    pub fn note_on(&mut self, frequency: f32) {
+        self.frequency = frequency;
+        for operator in &mut self.operators {
+            operator.trigger();
+        }
+    }
+
+    pub fn note_off(&mut self) {
+        for operator in &mut self.operators {
+            operator.envelope.release();
+        }
+    }
+
+    // False once the envelopes of all carriers have finished
+    pub fn is_active(&self) -> bool {
+        self.algorithm
+            .carriers
+            .iter()
+            .any(|&i| !matches!(self.operators[i].envelope.state(), EnvelopeState::Idle))
+    }
+
+    // Next sample, the carriers are averaged so the output stays within [-1, 1] for carrier
+    // levels of at most 1
+    pub fn run(&mut self) -> f32 {
+        self.modulation.fill(0.0);
+        let mut output = 0.0;
+        for i in (0..self.operators.len()).rev() {
+            let value = self.operators[i].run(self.frequency, self.modulation[i]);
+            for &(from, to) in &self.algorithm.modulations {
+                if from == i {
+                    self.modulation[to] += value;
+                }
+            }
+            if self.algorithm.carriers.contains(&i) {
+                output += value;
+            }
+        }
+
+        output / self.algorithm.carriers.len().max(1) as f32
+    }
+}
@@ -1,39 +1,1222 @@
+pub mod band_energy;
+pub mod beat_tracker;
 pub mod decoder;
+pub mod encoder;
 pub mod envelope;
 pub mod fft;
+pub mod fm;
 pub mod metronome;
 pub mod noise;
 pub mod noise_generator;
 pub mod oscillator;
+pub mod resample;
+pub mod spectrogram;
+pub mod spectrum;
+pub mod stft;
+pub mod streaming_decoder;
+pub mod tempo;
 pub mod time_signature;
 pub mod utils;
+pub mod wavetable;
+pub mod window;
 
 #[cfg(test)]
 mod tests {
     use super::decoder::*;
-    const PATH: &str = "sample.wav";
+    use super::fft::*;
+    use super::streaming_decoder::StreamingDecoder;
+    use num::complex::Complex;
+    const PATH: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/audio_samples/sample.wav"
+    );
+
+    // Unique per test run, so parallel runs don't write over each other's files
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("kopek_{}_{}.wav", name, std::process::id()))
+    }
+
     #[test]
     fn first_frame_test() {
-        let frames = decode(PATH);
-        assert_eq!([79, 79], frames[0]);
+        let audio = decode(PATH);
+        assert_eq!([79, 79], audio.frames[0]);
     }
 
     #[test]
     fn frame_length_test() {
-        let frames = decode(PATH);
-        assert_eq!(124443, frames.len());
+        let audio = decode(PATH);
+        assert_eq!(124443, audio.frames.len());
     }
 
     #[test]
     fn get_duration_in_seconds_test() {
-        let frames = decode(PATH);
-        assert_eq!(2.0, duration_in_seconds(frames, 44100.0))
+        let audio = decode(PATH);
+        assert_eq!(124443.0 / 44100.0, duration_in_seconds(&audio))
     }
 
     #[test]
     fn detect_bpm_test() {
-        let frames = decode(PATH);
-        let bpm = detect_bpm(frames, 44100.0);
-        assert_eq!(83, bpm);
+        let audio = decode(PATH);
+        // The loop is about 85 BPM but counting hits reads it faster, so only check that
+        // beats are found
+        assert!(detect_bpm(&audio).is_some());
+
+        for sample_rate in [22050, 44100, 48000, 96000] {
+            let bpm = detect_bpm(&clicks(sample_rate, 6.0, 100.0)).unwrap();
+            assert!((bpm - 100.0).abs() < 1.0, "{} Hz: {}", sample_rate, bpm);
+        }
+    }
+
+    fn clicks(sample_rate: u32, seconds: f32, bpm: f32) -> DecodedAudio<f32> {
+        let period = (sample_rate as f32 * 60.0 / bpm) as usize;
+        let frames = (0..(sample_rate as f32 * seconds) as usize)
+            // Clicks are offset by a quarter beat so they don't straddle two blocks
+            .map(|i| match (i + period * 3 / 4) % period < 50 {
+                true => [0.9, 0.9],
+                false => [0.0, 0.0],
+            })
+            .collect();
+
+        DecodedAudio {
+            frames,
+            sample_rate,
+            channel_count: 2,
+            format: audrey::Format::Wav,
+            sample_format: SampleFormat::Float(32),
+        }
+    }
+
+    #[test]
+    fn detect_bpm_sample_rate_test() {
+        let bpm = detect_bpm(&clicks(48000, 3.0, 120.0)).unwrap();
+        assert!((bpm - 120.0).abs() < 1.0);
+
+        let bpm = detect_bpm(&clicks(22050, 2.5, 120.0)).unwrap();
+        assert!((bpm - 120.0).abs() < 1.0);
+
+        assert_eq!(None, detect_bpm(&clicks(44100, 0.3, 120.0)));
+    }
+
+    #[test]
+    fn description_test() {
+        let audio = decode(PATH);
+        assert_eq!(44100, audio.sample_rate);
+        assert_eq!(SampleFormat::Int(16), audio.sample_format);
+    }
+
+    #[test]
+    fn streaming_decoder_test() {
+        let audio = decode(PATH);
+        let mut decoder = StreamingDecoder::open(PATH, 1000).unwrap();
+        assert_eq!(Some(124443), decoder.total_frames());
+
+        let frames: Vec<[i16; 2]> = decoder.by_ref().flat_map(Result::unwrap).collect();
+        assert_eq!(audio.frames, frames);
+
+        decoder.seek(5000).unwrap();
+        let block = decoder.next_block().unwrap().unwrap();
+        assert_eq!(&audio.frames[5000..6000], &block[..]);
+    }
+
+    #[test]
+    fn streaming_decoder_bit_depth_test() {
+        use audrey::hound;
+        for bits_per_sample in [8, 24] {
+            let path = temp_path(&format!("streaming_{}", bits_per_sample));
+            let spec = hound::WavSpec {
+                channels: 2,
+                sample_rate: 44100,
+                bits_per_sample,
+                sample_format: hound::SampleFormat::Int,
+            };
+            let full_scale = (1 << (bits_per_sample - 1)) as f32;
+            let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+            for i in 0..3001 {
+                let x = (i as f32 * 0.03).sin() * 0.9;
+                writer.write_sample((x * full_scale) as i32).unwrap();
+                writer.write_sample((x * 0.5 * full_scale) as i32).unwrap();
+            }
+            writer.finalize().unwrap();
+
+            let audio = decode(&path);
+            let streamed: Vec<[i16; 2]> = StreamingDecoder::open(&path, 1000)
+                .unwrap()
+                .flat_map(Result::unwrap)
+                .collect();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(3001, audio.frames.len());
+            assert_eq!(audio.frames, streamed);
+            // Full scale, not the raw 8-bit values
+            let peak = streamed.iter().map(|f| f[0].unsigned_abs()).max().unwrap();
+            assert!(peak > 29000, "{} bits peak {}", bits_per_sample, peak);
+        }
+    }
+
+    #[test]
+    fn decode_f32_test() {
+        let audio = decode(PATH);
+        let audio_f32 = decode_f32(PATH);
+        assert_eq!(audio.frames.len(), audio_f32.frames.len());
+        assert_eq!(79.0 / 32768.0, audio_f32.frames[0][0]);
+        assert!(audio_f32
+            .frames
+            .iter()
+            .all(|f| (-1.0..=1.0).contains(&f[0]) && (-1.0..=1.0).contains(&f[1])));
+    }
+
+    #[test]
+    fn downmix_test() {
+        // One frame of 5.1 with only front left and center
+        let audio: MultichannelAudio = MultichannelAudio {
+            samples: vec![1000, 0, 1000, 500, 0, 0],
+            layout: channel_layout(audrey::Format::Wav, 6),
+            sample_rate: 44100,
+            format: audrey::Format::Wav,
+            sample_format: SampleFormat::Int(16),
+        };
+        let stereo = audio.downmix_stereo().unwrap();
+        // Each side has a total gain of 1 + 2 * 0.7071
+        assert!((stereo[0][0] - 707).abs() <= 1);
+        assert!((stereo[0][1] - 293).abs() <= 1);
+        assert_eq!(vec![500], audio.channel(3));
+        assert_eq!(vec![1000], audio.to_planar()[2]);
+    }
+
+    #[test]
+    fn encoder_round_trip_test() {
+        use super::encoder::*;
+        let frames: Vec<[f32; 2]> = (0..1001)
+            .map(|i| {
+                let x = (i as f32 * 0.05).sin() * 0.8;
+                [x, -x]
+            })
+            .collect();
+
+        for (format, tolerance) in [
+            (WavFormat::Pcm16, 1.0 / 32768.0),
+            (WavFormat::Pcm24, 1.0 / 8388608.0),
+            (WavFormat::Float32, 0.0),
+        ] {
+            let path = temp_path(&format!("{:?}", format));
+            write_stereo(&path, &frames, 48000, format).unwrap();
+            let audio = decode_f32(&path);
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(48000, audio.sample_rate);
+            assert_eq!(frames.len(), audio.frames.len());
+            for (a, b) in frames.iter().zip(&audio.frames) {
+                assert!((a[0] - b[0]).abs() <= tolerance);
+                assert!((a[1] - b[1]).abs() <= tolerance);
+            }
+        }
+    }
+
+    #[test]
+    fn rf64_header_test() {
+        use super::encoder::*;
+        let spec = WavSpec {
+            sample_rate: 48000,
+            channel_count: 2,
+            format: WavFormat::Pcm24,
+        };
+        let riff = header(&spec, 6 * 1000);
+        let rf64 = header(&spec, 6 * 1_000_000_000);
+        assert_eq!(riff.len(), rf64.len());
+        assert_eq!(b"RIFF", &riff[..4]);
+        assert_eq!(b"JUNK", &riff[12..16]);
+        assert_eq!(b"RF64", &rf64[..4]);
+        assert_eq!(b"ds64", &rf64[12..16]);
+        // data size in ds64
+        assert_eq!(6_000_000_000_u64.to_le_bytes(), rf64[28..36]);
+
+        // WAVE_FORMAT_EXTENSIBLE with 24 valid bits, a stereo mask and the PCM subformat
+        let fmt = &riff[48..];
+        assert_eq!(b"fmt ", &fmt[..4]);
+        assert_eq!(40_u32.to_le_bytes(), fmt[4..8]);
+        assert_eq!(0xFFFE_u16.to_le_bytes(), fmt[8..10]);
+        assert_eq!(22_u16.to_le_bytes(), fmt[24..26]);
+        assert_eq!(24_u16.to_le_bytes(), fmt[26..28]);
+        assert_eq!(3_u32.to_le_bytes(), fmt[28..32]);
+        assert_eq!([1, 0, 0, 0], fmt[32..36]);
+        assert_eq!(b"data", &fmt[48..52]);
+    }
+
+    #[test]
+    fn rf64_boundary_test() {
+        use super::encoder::*;
+        let spec = WavSpec {
+            sample_rate: 44100,
+            channel_count: 2,
+            format: WavFormat::Pcm16,
+        };
+        // Everything in the RIFF chunk but the data
+        let overhead = header(&spec, 0).len() as u64 - 8;
+        let largest = i32::MAX as u64 - overhead - 1;
+        let riff = header(&spec, largest);
+        assert_eq!(b"RIFF", &riff[..4]);
+        assert_eq!((i32::MAX as u32 - 1).to_le_bytes(), riff[4..8]);
+        assert_eq!(b"RF64", &header(&spec, largest + 2)[..4]);
+    }
+
+    #[test]
+    fn resample_test() {
+        use super::resample::*;
+        let sine =
+            |rate: f32, i: usize| (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / rate).sin();
+        let input: Vec<f32> = (0..44100).map(|i| sine(44100.0, i)).collect();
+
+        for (resample_type, tolerance) in [
+            (ResampleType::default(), 1e-3),
+            (ResampleType::Linear, 2e-2),
+        ] {
+            let output = resample(&input, 44100, 48000, resample_type);
+            assert_eq!(48000, output.len());
+            // Skip the edges where the input starts and stops abruptly
+            for (i, value) in output.iter().enumerate().take(47000).skip(1000) {
+                assert!((value - sine(48000.0, i)).abs() < tolerance);
+            }
+
+            // Streaming in blocks gives the same result
+            let mut resampler = Resampler::new(44100, 48000, resample_type);
+            let mut streamed = vec![];
+            for block in input.chunks(1000) {
+                resampler.process(block, &mut streamed);
+            }
+            resampler.flush(&mut streamed);
+            assert_eq!(output, streamed);
+        }
+    }
+
+    #[test]
+    fn decode_with_sample_rate_test() {
+        let audio = try_decode_with_sample_rate(PATH, 48000).unwrap();
+        assert_eq!(48000, audio.sample_rate);
+        assert_eq!(
+            (124443.0_f64 * 48000.0 / 44100.0).ceil() as usize,
+            audio.frames.len()
+        );
+    }
+
+    #[test]
+    fn estimate_tempo_test() {
+        for bpm in [90.0, 120.0, 150.0] {
+            let candidates = super::tempo::estimate_tempo(&clicks(44100, 10.0, bpm));
+            assert!((candidates[0].bpm - bpm).abs() < 1.0);
+            assert!(candidates
+                .windows(2)
+                .all(|c| c[0].confidence >= c[1].confidence));
+        }
+
+        assert!(super::tempo::estimate_tempo(&clicks(44100, 1.0, 120.0)).is_empty());
+    }
+
+    #[test]
+    fn track_beats_test() {
+        // Clicks are at a quarter of every period, every fourth click starting from the
+        // second one is louder
+        let mut audio = clicks(44100, 10.0, 120.0);
+        let period = 44100 / 2;
+        for (i, frame) in audio.frames.iter_mut().enumerate() {
+            if i / period % 4 != 1 {
+                frame[0] *= 0.3;
+                frame[1] *= 0.3;
+            }
+        }
+
+        let track = super::beat_tracker::track_beats(&audio, 4).unwrap();
+        assert!((track.bpm - 120.0).abs() < 1.0);
+        assert!(track.beats.len() >= 18);
+        for beat in &track.beats {
+            let distance = (*beat as i64 - period as i64 / 4).rem_euclid(period as i64);
+            assert!(distance.min(period as i64 - distance) < 1024);
+        }
+        for downbeat in &track.downbeats {
+            let click = (*downbeat as usize + period / 2) / period;
+            assert_eq!(1, click % 4);
+        }
+
+        // The metronome ticks shortly after every tracked beat, counting up by one per beat,
+        // and accents the downbeats. Stereo, so elapsed samples are twice the frames. It
+        // starts on a whole bar, the pickup beat before the first downbeat isn't counted
+        let mut time_signature = track.time_signature(4, 2);
+        let mut first_index = None;
+        let beats = track.beats.iter().skip_while(|&&b| b < track.downbeats[0]);
+        for (i, &beat) in beats.enumerate() {
+            let (on_beat, accent) = time_signature.update((beat as u32 + 2000) * 2);
+            assert!(on_beat, "beat {} at {}", i, beat);
+            assert_eq!(track.downbeats.contains(&beat), accent);
+            let index = time_signature.beat_index() as usize;
+            assert_eq!(i, index - *first_index.get_or_insert(index));
+        }
+    }
+
+    #[test]
+    fn metronome_fractional_tempo_test() {
+        use super::metronome::Metronome;
+
+        // 26276.46 samples per beat, a truncated period would be 460 samples early after
+        // 1000 beats
+        let mut metronome = Metronome::new(100, 44100, 1);
+        metronome.set_tempo(100.7);
+        let period = 44100.0 * 60.0 / 100.7;
+        metronome.update((1000.0 * period) as u32 - 10);
+        assert_eq!(999, metronome.beat_index());
+        assert!(!metronome.on_beat());
+        metronome.update((1000.0 * period) as u32 + 10);
+        assert_eq!(1000, metronome.beat_index());
+        assert!(metronome.on_beat());
+    }
+
+    fn assert_close(expected: &[Complex<f64>], actual: &[Complex<f64>], tolerance: f64) {
+        assert_eq!(expected.len(), actual.len());
+        for (e, a) in expected.iter().zip(actual) {
+            assert!((e - a).norm() < tolerance, "{} != {}", e, a);
+        }
+    }
+
+    // Share of the energy of samples in bins that are not harmonics of a fundamental with
+    // cycles whole cycles in samples
+    fn alias_energy(samples: &[f32], cycles: usize) -> f64 {
+        let input: Vec<Complex<f64>> = samples
+            .iter()
+            .map(|&x| Complex::new(x as f64, 0.0))
+            .collect();
+        let output = fft(&input);
+        let power = |k: usize| output[k].norm_sqr();
+        let half = samples.len() / 2;
+        let total: f64 = (1..=half).map(power).sum();
+        let alias: f64 = (1..=half).filter(|k| k % cycles != 0).map(power).sum();
+        alias / total
+    }
+
+    fn test_signal(n: usize) -> Vec<Complex<f64>> {
+        (0..n)
+            .map(|i| Complex::new((i as f64 * 0.37).sin(), (i as f64 * 0.11).cos() - 0.5))
+            .collect()
+    }
+
+    #[test]
+    fn ifft_round_trip_test() {
+        for n in [1, 2, 8, 1024] {
+            let input = test_signal(n);
+            assert_close(&input, &ifft(&fft(&input)), 1e-9);
+        }
+    }
+
+    #[test]
+    fn ifft_zero_padded_round_trip_test() {
+        // Padding is up to the caller, the round trip gives the padded input
+        for n in [3, 5, 100, 1000] {
+            let input = test_signal(n);
+            let output = ifft(&fft(&pad_to_power_of_two(&input)));
+            assert_eq!(n.next_power_of_two(), output.len());
+            assert_close(&input, &output[..n], 1e-9);
+            assert!(output[n..].iter().all(|c| c.norm() < 1e-9));
+        }
+    }
+
+    #[test]
+    fn ifft_normalization_test() {
+        // A unit impulse has a flat spectrum of ones, and a flat spectrum is an impulse
+        let mut impulse = vec![Complex::new(0.0, 0.0); 16];
+        impulse[0] = Complex::new(1.0, 0.0);
+        assert_close(&[Complex::new(1.0, 0.0); 16], &fft(&impulse), 1e-12);
+        assert_close(&impulse, &ifft(&[Complex::new(1.0, 0.0); 16]), 1e-12);
+    }
+
+    #[test]
+    fn rfft_test() {
+        for n in [1, 2, 7, 16, 100, 1024] {
+            let input: Vec<f64> = (0..n).map(|i| (i as f64 * 0.3).sin() + 0.25).collect();
+            let complex: Vec<Complex<f64>> = input.iter().map(|&x| Complex::new(x, 0.0)).collect();
+            let expected = fft(&complex);
+            let spectrum = rfft(&input);
+            assert_eq!(n / 2 + 1, spectrum.len());
+            assert_close(&expected[..spectrum.len()], &spectrum, 1e-9);
+
+            // The f32 path matches within single precision
+            let input: Vec<f32> = input.iter().map(|&x| x as f32).collect();
+            let spectrum: Vec<Complex<f64>> = rfft(&input)
+                .iter()
+                .map(|c| Complex::new(c.re as f64, c.im as f64))
+                .collect();
+            assert_close(&expected[..spectrum.len()], &spectrum, 1e-6 * n as f64);
+        }
+    }
+
+    #[test]
+    fn rfft_plan_test() {
+        // One plan and scratch buffer reused for many blocks gives the same as rfft
+        for n in [6, 9, 100, 1024] {
+            let plan = RfftPlan::new(n);
+            let mut scratch = vec![Complex::new(0.0, 0.0); plan.scratch_len()];
+            let mut spectrum = vec![Complex::new(0.0, 0.0); plan.spectrum_len()];
+            for block in 0..3 {
+                let input: Vec<f64> = (0..n).map(|i| ((i * (block + 1)) as f64).sin()).collect();
+                plan.forward_with_scratch(&input, &mut spectrum, &mut scratch);
+                assert_close(&rfft(&input), &spectrum, 1e-9);
+            }
+        }
+        assert_eq!(0, RfftPlan::<f32>::new(1024).scratch_len());
+    }
+
+    #[test]
+    fn irfft_round_trip_test() {
+        for n in [1, 2, 7, 16, 100, 1024] {
+            let input: Vec<f64> = (0..n).map(|i| (i as f64 * 0.7).cos() * 0.5).collect();
+            let output = irfft(&rfft(&input), n);
+            assert_eq!(n, output.len());
+            for (expected, sample) in input.iter().zip(&output) {
+                assert!((expected - sample).abs() < 1e-9);
+            }
+        }
+    }
+
+    fn dft(input: &[Complex<f64>]) -> Vec<Complex<f64>> {
+        let n = input.len() as f64;
+        (0..input.len())
+            .map(|k| {
+                input
+                    .iter()
+                    .enumerate()
+                    .map(|(i, x)| {
+                        x * Complex::from_polar(
+                            1.0,
+                            -2.0 * std::f64::consts::PI * (i * k) as f64 / n,
+                        )
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fft_plan_test() {
+        // Powers of two, mixed radix sizes and primes for Bluestein
+        for n in [1, 2, 4, 64, 512, 3, 6, 15, 100, 1000, 7, 13, 97, 202] {
+            let plan = FftPlan::new(n);
+            let input = test_signal(n);
+            let mut buffer = input.clone();
+            plan.forward(&mut buffer);
+            assert_close(&dft(&input), &buffer, 1e-9);
+            plan.inverse(&mut buffer);
+            assert_close(&input, &buffer, 1e-9);
+        }
+    }
+
+    #[test]
+    fn fft_plan_f32_test() {
+        for n in [64, 1000, 97] {
+            let input = test_signal(n);
+            let expected = fft(&input);
+            let plan = FftPlan::<f32>::new(n);
+            let mut buffer: Vec<Complex<f32>> = input
+                .iter()
+                .map(|c| Complex::new(c.re as f32, c.im as f32))
+                .collect();
+            plan.forward(&mut buffer);
+            let buffer: Vec<Complex<f64>> = buffer
+                .iter()
+                .map(|c| Complex::new(c.re as f64, c.im as f64))
+                .collect();
+            assert_close(&expected, &buffer, 1e-3);
+        }
+    }
+
+    #[test]
+    fn fft_plan_scratch_test() {
+        let plan = FftPlan::new(1000);
+        let input = test_signal(1000);
+        let mut buffer = input.clone();
+        let mut scratch = vec![Complex::new(0.0, 0.0); plan.scratch_len()];
+        plan.forward_with_scratch(&mut buffer, &mut scratch);
+        assert_close(&fft(&input), &buffer, 1e-9);
+        assert_eq!(0, FftPlan::<f64>::new(1024).scratch_len());
+    }
+
+    #[test]
+    fn window_test() {
+        use super::window::*;
+        let size = 4096;
+        // Coherent gain and ENBW in bins of the periodic windows
+        for (window_type, coherent_gain, enbw) in [
+            (WindowType::Rectangular, 1.0, 1.0),
+            (WindowType::Hann, 0.5, 1.5),
+            (WindowType::Hamming, 0.54, 1.3628),
+            (WindowType::Blackman, 0.42, 1.7268),
+            (WindowType::BlackmanHarris, 0.35875, 2.0044),
+            (WindowType::FlatTop, 0.21557895, 3.7702),
+        ] {
+            let window = Window::new(window_type, size);
+            assert!((window.coherent_gain() - coherent_gain).abs() < 1e-4);
+            assert!((window.enbw() - enbw).abs() < 1e-3, "{:?}", window_type);
+        }
+
+        // The edge cases of the parametric windows
+        let hann = Window::new(WindowType::Hann, 64);
+        let tukey = Window::new(WindowType::Tukey { alpha: 1.0 }, 64);
+        let flat = Window::new(WindowType::Tukey { alpha: 0.0 }, 64);
+        let kaiser = Window::new(WindowType::Kaiser { beta: 0.0 }, 64);
+        for i in 0..64 {
+            assert!((hann.coefficients()[i] - tukey.coefficients()[i]).abs() < 1e-12);
+            assert_eq!(1.0, flat.coefficients()[i]);
+            assert_eq!(1.0, kaiser.coefficients()[i]);
+        }
+        let kaiser = Window::new(WindowType::Kaiser { beta: 8.6 }, 64);
+        assert_eq!(1.0, kaiser.coefficients()[32]);
+        assert!(kaiser.coefficients()[0] < 1e-2);
+
+        let mut buffer = vec![2.0_f32; 64];
+        hann.apply(&mut buffer);
+        assert_eq!(0.0, buffer[0]);
+        assert_eq!(2.0, buffer[32]);
+    }
+
+    #[test]
+    fn stft_test() {
+        use super::stft::*;
+        let signal: Vec<f64> = (0..10_000)
+            .map(|i| (i as f64 * 0.05).sin() + 0.3 * (i as f64 * 0.31).cos())
+            .collect();
+        for (padding, hop_size) in [
+            (Padding::Zero, 256),
+            (Padding::Reflect, 512),
+            (Padding::Zero, 300),
+        ] {
+            let config = StftConfig {
+                frame_size: 1024,
+                hop_size,
+                window: super::window::WindowType::Hann,
+                padding,
+            };
+            let transform = stft(&signal, config);
+            assert_eq!(10_000 / hop_size + 1, transform.frame_count());
+            assert!(transform.frames.iter().all(|f| f.len() == 513));
+            let output = istft(&transform);
+            assert_eq!(signal.len(), output.len());
+            for (a, b) in signal.iter().zip(&output) {
+                assert!((a - b).abs() < 1e-9);
+            }
+        }
+
+        // Without padding only whole frames are analysed, the edges can't be reconstructed
+        let config = StftConfig {
+            padding: Padding::None,
+            ..Default::default()
+        };
+        let signal: Vec<f32> = signal.iter().map(|&x| x as f32).collect();
+        let transform = stft(&signal, config);
+        assert_eq!((10_000 - 2048) / 512 + 1, transform.frame_count());
+        let output = istft(&transform);
+        for (a, b) in signal.iter().zip(&output).take(9000).skip(1000) {
+            assert!((a - b).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn stft_audio_test() {
+        use super::stft::*;
+        // 1000 Hz falls between bins 46 and 47 of a 2048 point frame at 44100 Hz
+        let audio = DecodedAudio {
+            frames: (0..44100)
+                .map(|i| {
+                    let x = (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 44100.0).sin();
+                    [x, x]
+                })
+                .collect(),
+            sample_rate: 44100,
+            channel_count: 2,
+            format: audrey::Format::Wav,
+            sample_format: SampleFormat::Float(32),
+        };
+        let transform = stft_audio(&audio, StftConfig::default());
+        let frame = &transform.frames[transform.frame_count() / 2];
+        let peak = (0..frame.len())
+            .max_by(|&a, &b| frame[a].norm().total_cmp(&frame[b].norm()))
+            .unwrap();
+        assert!((46..=47).contains(&peak));
+    }
+
+    #[test]
+    fn spectrogram_test() {
+        use super::spectrogram::*;
+        let sample_rate = 48000;
+        // A 6 kHz tone at half of full scale, -6 dBFS
+        let signal: Vec<f32> = (0..sample_rate)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 6000.0 * i as f32 / 48000.0).sin())
+            .collect();
+        let config = SpectrogramConfig {
+            height: 100,
+            floor_db: -60.0,
+            colormap: Colormap::Grayscale,
+            ..Default::default()
+        };
+        let image = spectrogram(&signal, sample_rate as u32, &config);
+        assert_eq!(sample_rate / 512 + 1, image.width);
+        assert_eq!(100, image.height);
+
+        // 6 kHz is at a quarter of the way up to 24 kHz, row 75 from the top
+        let column: Vec<u8> = (0..100)
+            .map(|y| image.pixel(image.width / 2, y)[0])
+            .collect();
+        let brightest = (0..100).max_by_key(|&y| column[y]).unwrap();
+        assert_eq!(75, brightest);
+        // -6 dB out of 60 dB of range
+        assert!((column[75] as f32 - 0.9 * 255.0).abs() < 5.0);
+        assert_eq!(0, column[10]);
+
+        // On a log axis from 100 Hz the tone is at log(60) / log(240) of the height
+        let config = SpectrogramConfig {
+            frequency_scale: FrequencyScale::Log {
+                min_frequency: 100.0,
+            },
+            ..config
+        };
+        let image = spectrogram(&signal, sample_rate as u32, &config);
+        let brightest = (0..100)
+            .max_by_key(|&y| image.pixel(image.width / 2, y)[0])
+            .unwrap();
+        let expected = 100.0 * (1.0 - 60.0_f32.ln() / 240.0_f32.ln());
+        assert!((brightest as f32 - expected).abs() <= 1.0);
+
+        let ppm = image.to_ppm();
+        assert!(ppm.starts_with(format!("P6\n{} 100\n255\n", image.width).as_bytes()));
+        let png = image.to_png();
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &png[..8]);
+        assert_eq!(b"IHDR", &png[12..16]);
+        assert_eq!(b"IEND\xae\x42\x60\x82", &png[png.len() - 8..]);
+    }
+
+    #[test]
+    fn oscillator_phase_test() {
+        use super::oscillator::{Oscillator, WaveType};
+
+        let mut oscillator = Oscillator::new(48000.0);
+        oscillator.set_frequency(12000.0);
+
+        // Every wave type reads the same normalized phase, a quarter cycle per sample here
+        oscillator.set_phase(0.25);
+        assert!((oscillator.sine() - 1.0).abs() < 1e-6);
+        assert!((oscillator.phase() - 0.5).abs() < 1e-12);
+        assert_eq!(oscillator.sawtooth(), 0.0);
+        assert_eq!(oscillator.square(0.5), -1.0);
+        assert_eq!(oscillator.triangle(), -1.0);
+        assert!((oscillator.fake_sine() - 1.0).abs() < 1e-6);
+        assert!((oscillator.phase() - 0.5).abs() < 1e-12);
+
+        // Switching wave types mid-note carries on from the same point in the cycle
+        for wave_type in [
+            WaveType::Sine,
+            WaveType::FakeSine,
+            WaveType::Sawtooth,
+            WaveType::Square { duty: 0.5 },
+            WaveType::Triangle,
+        ] {
+            oscillator.set_wave_type(wave_type);
+            oscillator.run();
+        }
+        assert!((oscillator.phase() - 0.75).abs() < 1e-12);
+
+        oscillator.set_phase(-0.25);
+        assert!((oscillator.phase() - 0.75).abs() < 1e-12);
+        oscillator.set_phase(-1e-20);
+        assert!((0.0..1.0).contains(&oscillator.phase()));
+        oscillator.reset();
+        assert_eq!(oscillator.phase(), 0.0);
+        assert_eq!(oscillator.sawtooth(), -1.0);
+
+        // Ten minutes of 440 Hz at 44100 Hz stay in tune
+        let mut oscillator = Oscillator::new(44100.0);
+        oscillator.set_frequency(440.0);
+        let samples = 44100_u64 * 600;
+        for _ in 0..samples {
+            oscillator.sine();
+        }
+        let expected = (440 * samples % 44100) as f64 / 44100.0;
+        // Distance around the cycle, the phase may be just below 1 for an expected 0
+        let error = (oscillator.phase() - expected).rem_euclid(1.0);
+        assert!(error.min(1.0 - error) < 1e-6, "{}", oscillator.phase());
+    }
+
+    #[test]
+    fn oscillator_modulation_test() {
+        use super::oscillator::{Oscillator, WaveType};
+
+        let mut fixed = Oscillator::new(44100.0);
+        let mut modulated = Oscillator::new(44100.0);
+        fixed.set_frequency(440.0);
+        for wave_type in [WaveType::Sine, WaveType::Sawtooth, WaveType::Triangle] {
+            fixed.set_wave_type(wave_type);
+            modulated.set_wave_type(wave_type);
+            for _ in 0..1000 {
+                assert_eq!(fixed.run(), modulated.run_modulated(440.0, 0.0));
+            }
+        }
+
+        // A quarter cycle ahead, a sine turns into a cosine without moving the phase
+        modulated.set_wave_type(WaveType::Sine);
+        modulated.reset();
+        assert!((modulated.run_modulated(0.0, 0.25) - 1.0).abs() < 1e-6);
+        assert!((modulated.run_modulated(0.0, -0.25) + 1.0).abs() < 1e-6);
+        assert_eq!(modulated.phase(), 0.0);
+
+        // The frequency can change every sample, negative runs backwards
+        modulated.run_modulated(11025.0, 0.0);
+        assert!((modulated.phase() - 0.25).abs() < 1e-12);
+        modulated.run_modulated(-22050.0, 0.0);
+        assert!((modulated.phase() - 0.75).abs() < 1e-12);
+        assert_eq!(modulated.frequency(), 0.0);
+    }
+
+    #[test]
+    fn oscillator_process_test() {
+        use super::oscillator::{AntiAliasing, Oscillator, WaveType};
+
+        for anti_aliasing in [AntiAliasing::None, AntiAliasing::PolyBlep] {
+            for wave_type in [
+                WaveType::Sine,
+                WaveType::FakeSine,
+                WaveType::Sawtooth,
+                WaveType::Square { duty: 0.3 },
+                WaveType::Triangle,
+            ] {
+                let mut per_sample = Oscillator::new(44100.0);
+                let mut block = Oscillator::new(44100.0);
+                for oscillator in [&mut per_sample, &mut block] {
+                    // Not a divisor of the sample rate, so no sample falls exactly on the end of a
+                    // cycle, where rounding could put it on either side of a jump
+                    oscillator.set_frequency(443.0);
+                    oscillator.set_wave_type(wave_type);
+                    oscillator.set_anti_aliasing(anti_aliasing);
+                }
+
+                // Blocks of different sizes continue where the last one ended
+                let mut output = vec![0.0; 3000];
+                block.process(&mut output[..1000], None);
+                block.process(&mut output[1000..1001], None);
+                block.process(&mut output[1001..], None);
+                for value in &output {
+                    assert!((per_sample.run() - value).abs() < 1e-4, "{:?}", wave_type);
+                }
+                assert!((per_sample.phase() - block.phase()).abs() < 1e-9);
+
+                // A frequency per sample, the same as run_modulated
+                let frequencies: Vec<f32> = (0..1000).map(|i| 200.0 + i as f32).collect();
+                let mut output = vec![0.0; 1000];
+                block.process(&mut output, Some(&frequencies));
+                for (value, &frequency) in output.iter().zip(&frequencies) {
+                    let expected = per_sample.run_modulated(frequency, 0.0);
+                    assert!((expected - value).abs() < 1e-4, "{:?}", wave_type);
+                }
+
+                let mut stereo = vec![[0.0; 2]; 600];
+                block.process_stereo(&mut stereo, None);
+                for frame in &stereo {
+                    let expected = per_sample.run();
+                    assert!((expected - frame[0]).abs() < 1e-4 && frame[0] == frame[1]);
+                }
+                let frequencies = vec![1001.0; 600];
+                block.process_stereo(&mut stereo, Some(&frequencies));
+                for frame in &stereo {
+                    let expected = per_sample.run_modulated(1001.0, 0.0);
+                    assert!((expected - frame[0]).abs() < 1e-4 && frame[0] == frame[1]);
+                }
+            }
+        }
+
+        // Silent without a frequency, like run
+        let mut oscillator = Oscillator::new(44100.0);
+        let mut output = vec![1.0; 100];
+        oscillator.process(&mut output, None);
+        assert!(output.iter().all(|&value| value == 0.0));
+    }
+
+    #[test]
+    fn oscillator_anti_aliasing_test() {
+        use super::oscillator::{AntiAliasing, Oscillator};
+
+        // A whole number of cycles in n samples, so every harmonic and every alias lands
+        // exactly on a bin. About 3 kHz, where the naive waveforms alias audibly
+        let (n, cycles, sample_rate) = (4096, 280, 44100.0);
+        let energy = |anti_aliasing: AntiAliasing, wave: &dyn Fn(&mut Oscillator) -> f32| {
+            let mut oscillator = Oscillator::new(sample_rate);
+            oscillator.set_frequency(cycles as f32 * sample_rate / n as f32);
+            oscillator.set_anti_aliasing(anti_aliasing);
+            let samples: Vec<f32> = (0..n).map(|_| wave(&mut oscillator)).collect();
+            alias_energy(&samples, cycles)
+        };
+
+        type Wave<'a> = &'a dyn Fn(&mut Oscillator) -> f32;
+        let waves: [(&str, Wave); 4] = [
+            ("sawtooth", &|o| o.sawtooth()),
+            ("square", &|o| o.square(0.5)),
+            ("pulse", &|o| o.square(0.3)),
+            ("triangle", &|o| o.triangle()),
+        ];
+        for (name, wave) in waves {
+            let naive = energy(AntiAliasing::None, wave);
+            let band_limited = energy(AntiAliasing::PolyBlep, wave);
+            assert!(
+                band_limited < naive / 20.0,
+                "{}: {} vs {}",
+                name,
+                band_limited,
+                naive
+            );
+        }
+
+        // The sines are left alone
+        let mut naive = Oscillator::new(sample_rate);
+        let mut band_limited = Oscillator::new(sample_rate);
+        band_limited.set_anti_aliasing(AntiAliasing::PolyBlep);
+        for oscillator in [&mut naive, &mut band_limited] {
+            oscillator.set_frequency(3000.0);
+        }
+        for _ in 0..1000 {
+            assert_eq!(naive.sine(), band_limited.sine());
+        }
+    }
+
+    #[test]
+    fn envelope_test() {
+        use super::envelope::*;
+
+        let mut envelope = Envelope::new(1000.0, 1);
+        envelope.set_attack(0.01);
+        envelope.set_decay(0.02);
+        envelope.set_release(0.04);
+        assert_eq!(0.0, envelope.update());
+
+        envelope.press();
+        let attack: Vec<f32> = (0..10).map(|_| envelope.update()).collect();
+        assert!(attack.windows(2).all(|w| w[1] > w[0]));
+        assert_eq!(1.0, attack[9]);
+        // Decays to the sustain level and holds it for as long as the gate is on
+        for _ in 0..20 {
+            envelope.update();
+        }
+        assert!(matches!(envelope.state(), EnvelopeState::Sustain));
+        for _ in 0..1000 {
+            assert!((envelope.update() - 0.8).abs() < 1e-6);
+        }
+
+        envelope.release();
+        assert!(matches!(envelope.state(), EnvelopeState::Release));
+        let release: Vec<f32> = (0..40).map(|_| envelope.update()).collect();
+        assert!((release[0] - 0.8).abs() < 1e-6);
+        assert!(release.windows(2).all(|w| w[1] < w[0]));
+        assert!(matches!(envelope.state(), EnvelopeState::Idle));
+
+        // Released halfway through the attack, fades from there instead of jumping
+        envelope.press();
+        let attack: Vec<f32> = (0..5).map(|_| envelope.update()).collect();
+        envelope.release();
+        let release: Vec<f32> = (0..40).map(|_| envelope.update()).collect();
+        assert_eq!(attack[4], release[0]);
+        assert!(release.windows(2).all(|w| w[1] < w[0]));
+        assert_eq!(0.0, release[39]);
+        assert!(matches!(envelope.state(), EnvelopeState::Idle));
+    }
+
+    #[test]
+    fn fm_test() {
+        use super::fm::*;
+
+        let (n, sample_rate) = (4096, 44100.0);
+        let bin = sample_rate / n as f32;
+        // Amplitude of every bin of n samples of voice
+        let amplitudes = |voice: &mut FmVoice| {
+            let input: Vec<f32> = (0..n).map(|_| voice.run()).collect();
+            rfft(&input)
+                .iter()
+                .map(|c| 2.0 * c.norm() / n as f32)
+                .collect::<Vec<f32>>()
+        };
+        // Envelopes that stay close to full level for the whole test
+        let hold = |operator: &mut Operator| {
+            operator.envelope.set_attack(0.0);
+            operator.envelope.set_decay(1000.0);
+        };
+
+        // A carrier and a modulator at 3 times its frequency. With modulation index 1 the
+        // sidebands at f (1 + 3k) follow the Bessel functions, J0(1) = 0.765,
+        // J1(1) = 0.440 and J2(1) = 0.115. The ones at negative frequencies fold back
+        let mut voice = FmVoice::new(sample_rate, Algorithm::stack(2));
+        voice.operators_mut().iter_mut().for_each(hold);
+        voice.operator_mut(1).ratio = 3.0;
+        voice.operator_mut(1).level = 1.0;
+        voice.note_on(40.0 * bin);
+        voice.run();
+        let spectrum = amplitudes(&mut voice);
+        for (harmonic, expected) in [(1, 0.765), (2, 0.440), (3, 0.0), (4, 0.440), (5, 0.115)] {
+            let amplitude = spectrum[40 * harmonic];
+            assert!(
+                (amplitude - expected).abs() < 0.01,
+                "harmonic {}: {}",
+                harmonic,
+                amplitude
+            );
+        }
+
+        // Feedback adds harmonics to a single operator, without it is a pure sine
+        let mut voice = FmVoice::new(sample_rate, Algorithm::parallel(1));
+        voice.operators_mut().iter_mut().for_each(hold);
+        voice.note_on(40.0 * bin);
+        voice.run();
+        let pure = amplitudes(&mut voice);
+        assert!((pure[40] - 1.0).abs() < 0.01);
+        assert!(pure[80] < 1e-3);
+        voice.operator_mut(0).feedback = 1.0;
+        voice.note_on(40.0 * bin);
+        voice.run();
+        assert!(amplitudes(&mut voice)[80] > 0.1);
+
+        // Pairs of carrier and modulator are mixed
+        let algorithm = Algorithm::pairs(4);
+        assert_eq!(vec![0, 2], algorithm.carriers);
+        assert_eq!(vec![(1, 0), (3, 2)], algorithm.modulations);
+        assert_eq!(4, algorithm.operator_count());
+        let mut voice = FmVoice::new(sample_rate, Algorithm::parallel(2));
+        voice.operators_mut().iter_mut().for_each(hold);
+        voice.operator_mut(1).ratio = 2.0;
+        voice.note_on(40.0 * bin);
+        voice.run();
+        let spectrum = amplitudes(&mut voice);
+        assert!((spectrum[40] - 0.5).abs() < 0.01 && (spectrum[80] - 0.5).abs() < 0.01);
+
+        // Growing the algorithm adds operators, note_off lets the voice fade out
+        voice.set_algorithm(Algorithm::stack(3));
+        assert_eq!(3, voice.operators().len());
+        for operator in voice.operators_mut() {
+            operator.envelope.set_release(0.01);
+        }
+        voice.note_on(440.0);
+        for _ in 0..1000 {
+            voice.run();
+        }
+        assert!(voice.is_active());
+        voice.note_off();
+        for _ in 0..1000 {
+            voice.run();
+        }
+        assert!(!voice.is_active());
+    }
+
+    #[test]
+    fn wavetable_test() {
+        use super::oscillator::Oscillator;
+        use super::wavetable::*;
+        use std::f32::consts::PI;
+
+        let sine_cycle: Vec<f32> = (0..600)
+            .map(|i| (2.0 * PI * i as f32 / 600.0).sin())
+            .collect();
+        let saw_cycle: Vec<f32> = (0..TABLE_SIZE)
+            .map(|i| 2.0 * i as f32 / TABLE_SIZE as f32 - 1.0)
+            .collect();
+
+        // Cycles of any length play back at the oscillator's pitch
+        let mut oscillator = WavetableOscillator::new(Wavetable::new(&[&sine_cycle]), 44100.0);
+        oscillator.set_frequency(441.0);
+        for i in 0..1000 {
+            let expected = (2.0 * PI * 441.0 * i as f32 / 44100.0).sin();
+            assert!((oscillator.run() - expected).abs() < 1e-3);
+        }
+
+        // Alias energy at about 3 kHz, measured like for the band-limited Oscillator
+        let (n, cycles, sample_rate) = (4096, 280, 44100.0);
+        let frequency = cycles as f32 * sample_rate / n as f32;
+        let mut naive = Oscillator::new(sample_rate);
+        naive.set_frequency(frequency);
+        let naive: Vec<f32> = (0..n).map(|_| naive.sawtooth()).collect();
+        let naive = alias_energy(&naive, cycles);
+        let mut oscillator = WavetableOscillator::new(Wavetable::new(&[&saw_cycle]), sample_rate);
+        oscillator.set_frequency(frequency);
+        let band_limited: Vec<f32> = (0..n).map(|_| oscillator.run()).collect();
+        let band_limited = alias_energy(&band_limited, cycles);
+        assert!(
+            band_limited < naive / 100.0,
+            "{} vs {}",
+            band_limited,
+            naive
+        );
+
+        // The table for a high note only has harmonics below Nyquist
+        let wavetable = Wavetable::new(&[&saw_cycle]);
+        let table = wavetable.table(0, 5000.0, 44100.0);
+        let spectrum = rfft(table);
+        let highest = (1..spectrum.len())
+            .rev()
+            .find(|&k| spectrum[k].norm() > 1e-3)
+            .unwrap();
+        assert!(highest as f32 * 5000.0 < 22050.0, "{}", highest);
+        assert!(highest as f32 * 5000.0 > 22050.0 / 2.0, "{}", highest);
+
+        // Morphing crossfades the neighbouring frames
+        let inverted: Vec<f32> = sine_cycle.iter().map(|x| -x).collect();
+        let wavetable = Wavetable::new(&[&sine_cycle, &inverted, &sine_cycle]);
+        assert_eq!(3, wavetable.frame_count());
+        let mut oscillator = WavetableOscillator::new(wavetable, 44100.0);
+        oscillator.set_frequency(441.0);
+        for (morph, gain) in [
+            (0.0, 1.0),
+            (0.25, 0.0),
+            (0.5, -1.0),
+            (0.625, -0.5),
+            (1.0, 1.0),
+        ] {
+            oscillator.set_morph(morph);
+            oscillator.set_phase(0.25);
+            assert!((oscillator.run() - gain).abs() < 1e-3, "{} {}", morph, gain);
+        }
+
+        // Frames from a decoded file, one cycle after another
+        let samples: Vec<f32> = [sine_cycle.clone(), saw_cycle[..600].to_vec()].concat();
+        let path = temp_path("wavetable");
+        super::encoder::write_mono(&path, &samples, 44100, super::encoder::WavFormat::Float32)
+            .unwrap();
+        let wavetable = Wavetable::from_audio(&decode(&path), 600);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(2, wavetable.frame_count());
+        let mut oscillator = WavetableOscillator::new(wavetable, 44100.0);
+        oscillator.set_frequency(100.0);
+        oscillator.set_phase(0.25);
+        assert!((oscillator.run() - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn band_energy_test() {
+        use super::band_energy::{BandEnergyConfig, BandEnergyTracker};
+
+        let sample_rate = 44100;
+        // Faded in over 50 ms, a tone that starts abruptly spreads into every band
+        let sine = |frequency: f32, amplitude: f32, length: usize| -> Vec<f32> {
+            (0..length)
+                .map(|i| {
+                    let t = i as f32 / sample_rate as f32;
+                    let fade = 0.5 - 0.5 * (std::f32::consts::PI * (t / 0.05).min(1.0)).cos();
+                    fade * amplitude * (2.0 * std::f32::consts::PI * frequency * t).sin()
+                })
+                .collect()
+        };
+        let mut tracker = BandEnergyTracker::new(BandEnergyConfig::default(), sample_rate);
+        let band = tracker
+            .bands()
+            .iter()
+            .position(|band| band.low <= 1000.0 && 1000.0 < band.high)
+            .unwrap();
+
+        // Silence stays below the floor
+        assert!(tracker.process(&vec![0.0; 22050]).is_empty());
+        assert_eq!(tracker.loudest_band(), None);
+        assert!(tracker.levels().iter().all(|&level| level == 0.0));
+
+        // A tone after silence hits once in its band, in blocks of any size
+        let tone = sine(1000.0, 0.5, 44100);
+        let mut hits = vec![];
+        for block in tone.chunks(300) {
+            hits.extend(tracker.process(block));
+        }
+        assert_eq!(hits.len(), 1, "{:?}", hits);
+        assert_eq!(hits[0].band, band);
+        assert!(hits[0].strength > 1.0);
+        // Detected within the first frames of the tone
+        assert!(hits[0].sample < 22050 + 2048, "{:?}", hits[0]);
+
+        // A sine of amplitude 0.5 is at -6 dBFS
+        assert_eq!(tracker.loudest_band(), Some(band));
+        let db = tracker.energies_db()[band];
+        assert!((db + 6.02).abs() < 0.5, "{}", db);
+        assert!((tracker.levels()[band] - 1.0).abs() < 1e-3);
+        for (i, &level) in tracker.levels().iter().enumerate() {
+            assert!(i == band || level < 0.01, "band {} level {}", i, level);
+        }
+
+        // Quieter passages are measured against the fading peak
+        tracker.process(&sine(1000.0, 0.25, 44100));
+        let level = tracker.levels()[band];
+        assert!(level > 0.25 && level < 1.0, "{}", level);
+
+        // Release is slower than attack, the energy fades after the tone stops
+        let before = tracker.energies()[band];
+        tracker.process(&vec![0.0; 4410]);
+        let after = tracker.energies()[band];
+        assert!(
+            after < before && after > before * 0.1,
+            "{} {}",
+            before,
+            after
+        );
+
+        // A louder burst hits again
+        tracker.process(&vec![0.0; 44100]);
+        let hits = tracker.process(&sine(1000.0, 0.5, 4410));
+        assert_eq!(
+            hits.iter().map(|hit| hit.band).collect::<Vec<_>>(),
+            vec![band]
+        );
+
+        tracker.reset();
+        assert!(tracker.energies().iter().all(|&energy| energy == 0.0));
+    }
+
+    #[test]
+    fn spectrum_test() {
+        use super::spectrum::*;
+        assert_eq!(46.875, bin_width(48000, 1024));
+        assert_eq!(1500.0, bin_to_frequency(32, 48000, 1024));
+        assert_eq!(32.0, frequency_to_bin(1500.0, 48000, 1024));
+
+        // A full scale sine on a bin reads 0 dBFS through the window's coherent gain
+        let window = super::window::Window::new(super::window::WindowType::Hann, 1024);
+        let mut signal: Vec<f64> = (0..1024)
+            .map(|i| (2.0 * std::f64::consts::PI * 32.0 * i as f64 / 1024.0).sin())
+            .collect();
+        window.apply(&mut signal);
+        let spectrum = rfft(&signal);
+        let levels = dbfs_spectrum(&spectrum, 1024, window.coherent_gain());
+        assert!(levels[32].abs() < 1e-3);
+        assert!((levels[31] + 6.02).abs() < 1e-2);
+        assert!((db_to_amplitude(-6.0206) - 0.5).abs() < 1e-4);
+        let power = power_spectrum(&spectrum);
+        let magnitude = magnitude_spectrum(&spectrum);
+        assert!((power[32] - magnitude[32] * magnitude[32]).abs() < 1e-2);
+
+        let octaves = octave_bands(20.0, 20000.0);
+        let centers: Vec<f32> = octaves.iter().map(|b| b.center.round()).collect();
+        assert_eq!(
+            vec![32.0, 63.0, 126.0, 251.0, 501.0, 1000.0, 1995.0, 3981.0, 7943.0, 15849.0],
+            centers
+        );
+        assert!((octaves[5].high / octaves[5].low - 10.0_f32.powf(0.3)).abs() < 1e-5);
+        // The 31 standard bands from 20 Hz to 20 kHz
+        assert_eq!(31, third_octave_bands(20.0, 20000.0).len());
+        let bands = log_bands(100.0, 10000.0, 4);
+        assert!((bands[1].low - 316.23).abs() < 0.01);
+        assert_eq!(10000.0, bands[3].high.round());
+
+        // Bins 0..8 of a 16 point spectrum at 16 Hz are 1 Hz apart
+        let values = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0];
+        // The second band falls between bins and is interpolated at its center
+        let bands = [Band::new(2.0, 5.0), Band::new(5.2, 5.8)];
+        assert_eq!(
+            vec![9.0, bands[1].center],
+            aggregate_bands(&values, &bands, 16, 16, Aggregation::Sum)
+        );
+        assert_eq!(
+            vec![3.0, bands[1].center],
+            aggregate_bands(&values, &bands, 16, 16, Aggregation::Mean)
+        );
+        assert_eq!(
+            4.0,
+            aggregate_bands(&values, &bands, 16, 16, Aggregation::Max)[0]
+        );
+    }
+
+    #[test]
+    fn missing_file_test() {
+        let result = try_decode("missing.wav");
+        assert!(matches!(result, Err(DecodeError::FileNotFound(_))));
     }
 }
@@ -3,29 +3,54 @@ pub struct Metronome {
     pub is_running: bool,
     beat_index: u32,
     sample_count: u32,
-    tick_period: f32,
+    // Fractional, beats are placed in f64 so they don't drift with fractional tempos
+    tick_period: f64,
+    // Samples before the first beat
+    offset: u32,
+    sample_rate: u32,
+    channel_count: u32,
     on_beat: bool,
 }
 
 impl Metronome {
     pub fn new(bpm: u16, sample_rate: u32, channel_count: u32) -> Self {
-        let tick_period = (sample_rate * channel_count * 60) as f32 / bpm as f32;
+        let tick_period = (sample_rate * channel_count * 60) as f64 / bpm as f64;
         Self {
             is_running: false,
             beat_index: 0,
             sample_count: 0,
             tick_period,
+            offset: 0,
+            sample_rate,
+            channel_count,
             on_beat: false,
         }
     }
 
+    // Fractional tempos, e.g. from beat_tracker
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.tick_period = (self.sample_rate * self.channel_count * 60) as f64 / bpm as f64;
+    }
+
+    // Moves the beats later by offset samples to line them up with a track
+    pub fn set_offset(&mut self, offset: u32) {
+        self.offset = offset;
+    }
+
     // update can be called from the main thread
     pub fn update(&mut self, elapsed_samples: u32) {
-        self.sample_count = elapsed_samples;
+        if elapsed_samples < self.offset {
+            self.sample_count = 0;
+            self.on_beat = false;
+            self.beat_index = 0;
+            return;
+        }
+        self.sample_count = elapsed_samples - self.offset;
 
-        let remainder = self.sample_count % self.tick_period as u32;
-        self.on_beat = remainder > 0 && remainder < 8192;
-        self.beat_index = self.sample_count / self.tick_period as u32;
+        let sample_count = self.sample_count as f64;
+        self.beat_index = (sample_count / self.tick_period) as u32;
+        let remainder = sample_count - self.beat_index as f64 * self.tick_period;
+        self.on_beat = remainder > 0.0 && remainder < 8192.0;
     }
 
     // Current number of beats played, similar to elapsed time
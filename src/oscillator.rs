@@ -2,7 +2,10 @@ pub struct Oscillator {
     sample_rate: f32,
     frequency: f32,
     wave_type: WaveType,
-    phase: f32,
+    anti_aliasing: AntiAliasing,
+    // Position in the cycle in [0, 1), shared by all wave types. Kept in f64 so the pitch
+    // doesn't drift from rounding over long runs
+    phase: f64,
 }
 
 impl Oscillator {
@@ -11,6 +14,7 @@ impl Oscillator {
             sample_rate,
             frequency: 0.0,
             wave_type: WaveType::Sine,
+            anti_aliasing: AntiAliasing::None,
             phase: 0.0,
         }
     }
@@ -27,10 +31,36 @@ impl Oscillator {
         self.wave_type
     }
 
+    // The phase carries over, so the wave type can change mid-note without a jump in time
     pub fn set_wave_type(&mut self, wave_type: WaveType) {
         self.wave_type = wave_type;
     }
 
+    pub fn anti_aliasing(&self) -> AntiAliasing {
+        self.anti_aliasing
+    }
+
+    // Only affects sawtooth, square and triangle, the sines have no harmonics to alias
+    pub fn set_anti_aliasing(&mut self, anti_aliasing: AntiAliasing) {
+        self.anti_aliasing = anti_aliasing;
+    }
+
+    // Position of the next sample in the cycle, 0 is the start of every wave type and 1 a
+    // whole cycle
+    pub fn phase(&self) -> f64 {
+        self.phase
+    }
+
+    // Wrapped into [0, 1), e.g. 0.25 starts a sine at its peak
+    pub fn set_phase(&mut self, phase: f64) {
+        self.phase = wrap(phase);
+    }
+
+    // Restarts the cycle, for retriggering a note
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
     pub fn run(&mut self) -> f32 {
         if self.frequency < f32::EPSILON {
             return 0.0;
@@ -44,50 +74,226 @@ impl Oscillator {
         }
     }
 
-    pub fn sine(&mut self) -> f32 {
-        let value = self.phase.sin();
-        let phase_increment = 2.0 * std::f32::consts::PI * self.frequency / self.sample_rate;
-        self.phase = (self.phase + phase_increment) % (2.0 * std::f32::consts::PI);
+    // One sample with the frequency and a phase offset in cycles given just for it, the
+    // inputs for frequency and phase modulation. The wave type and anti-aliasing are the
+    // ones set, the frequency set with set_frequency is left alone. Negative frequencies
+    // run the cycle backwards, as needed for through-zero FM
+    pub fn run_modulated(&mut self, frequency: f32, phase_offset: f32) -> f32 {
+        let increment = frequency as f64 / self.sample_rate as f64;
+        let phase = wrap(self.phase + phase_offset as f64);
+        let value = wave(
+            self.wave_type,
+            self.anti_aliasing,
+            phase,
+            increment.abs() as f32,
+        );
+        self.advance(increment);
 
         value
     }
 
+    // Fills output with the set wave type, like calling run for every sample. frequencies,
+    // when given, holds the frequency of every sample like run_modulated and must be as
+    // long as output
+    pub fn process(&mut self, output: &mut [f32], frequencies: Option<&[f32]>) {
+        if let Some(frequencies) = frequencies {
+            assert_eq!(
+                output.len(),
+                frequencies.len(),
+                "frequencies must have a value for every sample"
+            );
+        }
+        let anti_aliasing = self.anti_aliasing;
+        // Matched once per block, so every wave type gets a loop of its own
+        match self.wave_type {
+            WaveType::Sine => self.fill(output, frequencies, |phase, dt| {
+                wave(WaveType::Sine, anti_aliasing, phase, dt)
+            }),
+            WaveType::FakeSine => self.fill(output, frequencies, |phase, dt| {
+                wave(WaveType::FakeSine, anti_aliasing, phase, dt)
+            }),
+            WaveType::Sawtooth => self.fill(output, frequencies, |phase, dt| {
+                wave(WaveType::Sawtooth, anti_aliasing, phase, dt)
+            }),
+            WaveType::Square { duty } => self.fill(output, frequencies, |phase, dt| {
+                wave(WaveType::Square { duty }, anti_aliasing, phase, dt)
+            }),
+            WaveType::Triangle => self.fill(output, frequencies, |phase, dt| {
+                wave(WaveType::Triangle, anti_aliasing, phase, dt)
+            }),
+        }
+    }
+
+    // Same as process, with the value in both channels of every frame
+    pub fn process_stereo(&mut self, output: &mut [[f32; 2]], frequencies: Option<&[f32]>) {
+        if let Some(frequencies) = frequencies {
+            assert_eq!(
+                output.len(),
+                frequencies.len(),
+                "frequencies must have a value for every sample"
+            );
+        }
+        const BLOCK_SIZE: usize = 256;
+        let mut block = [0.0; BLOCK_SIZE];
+        for (i, frames) in output.chunks_mut(BLOCK_SIZE).enumerate() {
+            let block = &mut block[..frames.len()];
+            let start = i * BLOCK_SIZE;
+            self.process(
+                block,
+                frequencies.map(|frequencies| &frequencies[start..start + frames.len()]),
+            );
+            for (frame, &value) in frames.iter_mut().zip(block.iter()) {
+                *frame = [value; 2];
+            }
+        }
+    }
+
+    pub fn sine(&mut self) -> f32 {
+        self.next(WaveType::Sine)
+    }
+
     // https://bmtechjournal.wordpress.com/2020/05/27/super-fast-quadratic-sinusoid-approximation/
+    // Follows sine, so the two can be swapped without flipping the polarity
     pub fn fake_sine(&mut self) -> f32 {
-        let x = (self.phase / std::f32::consts::PI) - 1.0; // x in [-1, 1]
-        let value = 4.0 * x * (1.0 - x.abs());
-        let phase_increment = 2.0 * std::f32::consts::PI * self.frequency / self.sample_rate;
-        self.phase = (self.phase + phase_increment) % (2.0 * std::f32::consts::PI);
-
-        value
+        self.next(WaveType::FakeSine)
     }
 
     pub fn sawtooth(&mut self) -> f32 {
-        let normalized_phase = self.phase / (2.0 * std::f32::consts::PI);
-        let value = 2.0 * normalized_phase - 1.0;
-        let phase_increment = 2.0 * std::f32::consts::PI * self.frequency / self.sample_rate;
-        self.phase = (self.phase + phase_increment) % (2.0 * std::f32::consts::PI);
-
-        value
+        self.next(WaveType::Sawtooth)
     }
 
     // duty is between 0 and 1
     pub fn square(&mut self, duty: f32) -> f32 {
-        let duty = duty.clamp(0.0, 1.0);
-        let value = if self.phase < duty { 1.0 } else { -1.0 };
+        self.next(WaveType::Square { duty })
+    }
+
+    pub fn triangle(&mut self) -> f32 {
+        self.next(WaveType::Triangle)
+    }
 
-        let phase_increment = self.frequency / self.sample_rate;
-        self.phase = (self.phase + phase_increment).fract();
+    fn next(&mut self, wave_type: WaveType) -> f32 {
+        let increment = self.frequency as f64 / self.sample_rate as f64;
+        let value = wave(wave_type, self.anti_aliasing, self.phase, increment as f32);
+        self.advance(increment);
 
         value
     }
 
-    pub fn triangle(&mut self) -> f32 {
-        let value = 1.0 - 4.0 * (self.phase - 0.5).abs();
-        let phase_increment = self.frequency / self.sample_rate;
-        self.phase = (self.phase + phase_increment).fract();
+    fn fill<F: Fn(f64, f32) -> f32>(
+        &mut self,
+        output: &mut [f32],
+        frequencies: Option<&[f32]>,
+        wave: F,
+    ) {
+        match frequencies {
+            Some(frequencies) => {
+                for (value, &frequency) in output.iter_mut().zip(frequencies) {
+                    let increment = frequency as f64 / self.sample_rate as f64;
+                    *value = wave(self.phase, increment.abs() as f32);
+                    self.advance(increment);
+                }
+            }
+            // Silent below the lowest frequency, like run
+            None if self.frequency < f32::EPSILON => output.fill(0.0),
+            None => {
+                // The phase of every sample is computed from the start of the block instead
+                // of accumulated, so the samples don't depend on each other
+                let increment = self.frequency as f64 / self.sample_rate as f64;
+                let start = self.phase;
+                for (i, value) in output.iter_mut().enumerate() {
+                    *value = wave(wrap(start + i as f64 * increment), increment as f32);
+                }
+                self.advance(output.len() as f64 * increment);
+            }
+        }
+    }
 
-        value
+    fn advance(&mut self, increment: f64) {
+        self.phase = wrap(self.phase + increment);
+    }
+}
+
+// Value of wave_type at phase, for a phase advancing by dt per sample
+fn wave(wave_type: WaveType, anti_aliasing: AntiAliasing, phase: f64, dt: f32) -> f32 {
+    let t = phase as f32;
+    let band_limited = anti_aliasing == AntiAliasing::PolyBlep;
+    match wave_type {
+        WaveType::Sine => (2.0 * std::f32::consts::PI * t).sin(),
+        WaveType::FakeSine => {
+            let x = 1.0 - 2.0 * t; // x in (-1, 1]
+            4.0 * x * (1.0 - x.abs())
+        }
+        WaveType::Sawtooth => {
+            let mut value = 2.0 * t - 1.0;
+            if band_limited {
+                value -= poly_blep(t, dt);
+            }
+            value
+        }
+        WaveType::Square { duty } => {
+            let duty = duty.clamp(0.0, 1.0);
+            let mut value = if t < duty { 1.0 } else { -1.0 };
+            if band_limited {
+                // Rising edge at 0, falling edge at duty
+                value += poly_blep(t, dt) - poly_blep((t - duty + 1.0).fract(), dt);
+            }
+            value
+        }
+        WaveType::Triangle => {
+            let mut value = 1.0 - 4.0 * (t - 0.5).abs();
+            if band_limited {
+                // The slope changes by 8 dt per sample at the corners, up at 0 and down at
+                // 0.5. poly_blamp is scaled like poly_blep, for a change of 2
+                value += 4.0 * dt * (poly_blamp(t, dt) - poly_blamp((t + 0.5).fract(), dt));
+            }
+            value
+        }
+    }
+}
+
+// Phase wrapped into [0, 1)
+fn wrap(phase: f64) -> f64 {
+    let phase = phase - phase.floor();
+    // Tiny negative phases round up to exactly 1.0
+    match phase < 1.0 {
+        true => phase,
+        false => 0.0,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AntiAliasing {
+    // Plain waveforms, harmonics above Nyquist fold back as inharmonic partials
+    None,
+    // Polynomial band-limited steps smooth the jumps of sawtooth and square and the corners
+    // of triangle over the two samples around them. Cheap, and removes most of the
+    // audible aliasing for all but the highest notes
+    PolyBlep,
+}
+
+// Correction for a jump of -2 at phase 0, for a phase t in [0, 1) advancing by dt per sample
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let x = t / dt;
+        2.0 * x - x * x - 1.0
+    } else if t > 1.0 - dt {
+        let x = (t - 1.0) / dt;
+        x * x + 2.0 * x + 1.0
+    } else {
+        0.0
+    }
+}
+
+// Integral of poly_blep, the correction for a corner where the slope jumps at phase 0
+fn poly_blamp(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let x = t / dt - 1.0;
+        -x * x * x / 3.0
+    } else if t > 1.0 - dt {
+        let x = (t - 1.0) / dt + 1.0;
+        x * x * x / 3.0
+    } else {
+        0.0
     }
 }
 
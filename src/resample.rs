@@ -0,0 +1,209 @@
+use std::f64::consts::PI;
+
+// Kernel table resolution, kernel values between the entries are interpolated linearly
+const TABLE_STEPS_PER_ZERO_CROSSING: usize = 512;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResampleType {
+    // Cheap, but aliases and slightly dulls high frequencies
+    Linear,
+    // Windowed sinc with half_width zero crossings on each side, 16 to 64 is a good range
+    Sinc { half_width: usize },
+}
+
+impl Default for ResampleType {
+    fn default() -> Self {
+        ResampleType::Sinc { half_width: 32 }
+    }
+}
+
+// Streaming converter for a single channel, it keeps enough history between calls to process
+// so blocks can be of any size. Call flush at the end of the stream to get the tail out.
+pub struct Resampler {
+    resample_type: ResampleType,
+    // Input samples per output sample
+    step: f64,
+    // Lowpass cutoff relative to the input Nyquist frequency, below 1 when downsampling
+    cutoff: f64,
+    // Number of input samples on each side of the output position that contribute to it
+    reach: usize,
+    kernel: Vec<f64>,
+    buffer: Vec<f32>,
+    // Number of samples dropped from the front of buffer
+    offset: u64,
+    input_count: u64,
+    output_count: u64,
+}
+
+impl Resampler {
+    pub fn new(from_rate: u32, to_rate: u32, resample_type: ResampleType) -> Self {
+        assert!(
+            from_rate > 0 && to_rate > 0,
+            "sample rates must be positive"
+        );
+        let step = from_rate as f64 / to_rate as f64;
+        let cutoff = (to_rate as f64 / from_rate as f64).min(1.0);
+        let (reach, kernel) = match resample_type {
+            ResampleType::Linear => (1, vec![]),
+            ResampleType::Sinc { half_width } => {
+                let half_width = half_width.max(1);
+                let reach = (half_width as f64 / cutoff).ceil() as usize;
+                (reach, sinc_table(half_width))
+            }
+        };
+
+        Self {
+            resample_type,
+            step,
+            cutoff,
+            reach,
+            kernel,
+            // Samples before the start of the stream are silent
+            buffer: vec![0.0; reach],
+            offset: 0,
+            input_count: 0,
+            output_count: 0,
+        }
+    }
+
+    // Appends the converted samples to output
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        self.buffer.extend_from_slice(input);
+        self.input_count += input.len() as u64;
+        self.run(output, u64::MAX);
+    }
+
+    // Pads the stream with silence so the last input samples come out, the resampler
+    // can be reused for a new stream afterwards
+    pub fn flush(&mut self, output: &mut Vec<f32>) {
+        let expected = (self.input_count as f64 / self.step).ceil() as u64;
+        while self.output_count < expected {
+            self.buffer.extend(std::iter::repeat_n(0.0, self.reach + 1));
+            self.run(output, expected);
+        }
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.buffer.resize(self.reach, 0.0);
+        self.offset = 0;
+        self.input_count = 0;
+        self.output_count = 0;
+    }
+
+    // Number of output samples the converter lags behind its input
+    pub fn latency(&self) -> usize {
+        (self.reach as f64 / self.step).ceil() as usize
+    }
+
+    // Time of the next output sample in input samples, relative to the start of buffer.
+    // Derived from the output count instead of accumulated, so it doesn't drift and
+    // doesn't depend on the block sizes passed to process
+    fn position(&self) -> f64 {
+        (self.reach as f64 + self.output_count as f64 * self.step) - self.offset as f64
+    }
+
+    fn run(&mut self, output: &mut Vec<f32>, limit: u64) {
+        while self.output_count < limit
+            && self.position() + (self.reach as f64) < self.buffer.len() as f64
+        {
+            let position = self.position();
+            let value = match self.resample_type {
+                ResampleType::Linear => {
+                    let index = position.floor() as usize;
+                    let t = (position - index as f64) as f32;
+                    self.buffer[index] + t * (self.buffer[index + 1] - self.buffer[index])
+                }
+                ResampleType::Sinc { .. } => self.interpolate_sinc(position),
+            };
+            output.push(value);
+            self.output_count += 1;
+        }
+
+        // Drop the samples that can't contribute to any later output
+        let consumed = (self.position().floor() as usize).saturating_sub(self.reach);
+        let consumed = consumed.min(self.buffer.len());
+        self.buffer.drain(..consumed);
+        self.offset += consumed as u64;
+    }
+
+    fn interpolate_sinc(&self, position: f64) -> f32 {
+        let center = position.floor() as usize;
+        let fraction = position - center as f64;
+        let start = center + 1 - self.reach;
+        let mut sum = 0.0;
+        for (i, &sample) in self.buffer[start..=center + self.reach].iter().enumerate() {
+            let distance = (start + i) as f64 - center as f64 - fraction;
+            sum += sample as f64 * self.kernel_at(distance * self.cutoff);
+        }
+
+        (sum * self.cutoff) as f32
+    }
+
+    // Kernel value at x zero crossings from the center, 0 outside of the table
+    fn kernel_at(&self, x: f64) -> f64 {
+        let index = x.abs() * TABLE_STEPS_PER_ZERO_CROSSING as f64;
+        let i = index as usize;
+        if i + 1 >= self.kernel.len() {
+            return 0.0;
+        }
+        let t = index - i as f64;
+        self.kernel[i] + t * (self.kernel[i + 1] - self.kernel[i])
+    }
+}
+
+// Right half of a Blackman windowed sinc, sampled TABLE_STEPS_PER_ZERO_CROSSING times per
+// zero crossing
+fn sinc_table(half_width: usize) -> Vec<f64> {
+    let length = half_width * TABLE_STEPS_PER_ZERO_CROSSING + 1;
+    (0..=length)
+        .map(|i| {
+            let x = i as f64 / TABLE_STEPS_PER_ZERO_CROSSING as f64;
+            if x >= half_width as f64 {
+                return 0.0;
+            }
+            let sinc = if i == 0 {
+                1.0
+            } else {
+                (PI * x).sin() / (PI * x)
+            };
+            // Blackman window centered on 0
+            let w = 0.5 + 0.5 * x / half_width as f64;
+            let window = 0.42 - 0.5 * (2.0 * PI * w).cos() + 0.08 * (4.0 * PI * w).cos();
+            sinc * window
+        })
+        .collect()
+}
+
+pub fn resample(
+    input: &[f32],
+    from_rate: u32,
+    to_rate: u32,
+    resample_type: ResampleType,
+) -> Vec<f32> {
+    if from_rate == to_rate {
+        return input.to_vec();
+    }
+    let mut resampler = Resampler::new(from_rate, to_rate, resample_type);
+    let mut output =
+        Vec::with_capacity((input.len() as f64 * to_rate as f64 / from_rate as f64) as usize + 1);
+    resampler.process(input, &mut output);
+    resampler.flush(&mut output);
+
+    output
+}
+
+pub fn resample_frames(
+    frames: &[[f32; 2]],
+    from_rate: u32,
+    to_rate: u32,
+    resample_type: ResampleType,
+) -> Vec<[f32; 2]> {
+    let left: Vec<f32> = frames.iter().map(|f| f[0]).collect();
+    let right: Vec<f32> = frames.iter().map(|f| f[1]).collect();
+    let left = resample(&left, from_rate, to_rate, resample_type);
+    let right = resample(&right, from_rate, to_rate, resample_type);
+
+    left.into_iter().zip(right).map(|(l, r)| [l, r]).collect()
+}
@@ -0,0 +1,266 @@
+use crate::decoder::{DecodedAudio, DecoderSample};
+use crate::fft::FftFloat;
+use crate::spectrum;
+use crate::stft::{self, Stft, StftConfig};
+use crate::window::Window;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrequencyScale {
+    Linear,
+    // Equal space per octave from min_frequency up to Nyquist
+    Log { min_frequency: f32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colormap {
+    Grayscale,
+    Viridis,
+    Magma,
+}
+
+impl Colormap {
+    // Color for t in [0, 1], low to high level
+    pub fn color(&self, t: f32) -> [u8; 3] {
+        let anchors: &[[u8; 3]] = match self {
+            Colormap::Grayscale => &[[0, 0, 0], [255, 255, 255]],
+            // Sampled from the matplotlib colormaps of the same name
+            Colormap::Viridis => &[
+                [68, 1, 84],
+                [71, 44, 122],
+                [59, 81, 139],
+                [44, 113, 142],
+                [33, 144, 141],
+                [39, 173, 129],
+                [92, 200, 99],
+                [170, 220, 50],
+                [253, 231, 37],
+            ],
+            Colormap::Magma => &[
+                [0, 0, 4],
+                [28, 16, 68],
+                [79, 18, 123],
+                [129, 37, 129],
+                [181, 54, 122],
+                [229, 80, 100],
+                [251, 135, 97],
+                [254, 194, 135],
+                [252, 253, 191],
+            ],
+        };
+        let position = t.clamp(0.0, 1.0) * (anchors.len() - 1) as f32;
+        let i = (position as usize).min(anchors.len() - 2);
+        let fraction = position - i as f32;
+        let mut color = [0; 3];
+        for (c, (a, b)) in color.iter_mut().zip(anchors[i].iter().zip(&anchors[i + 1])) {
+            *c = (*a as f32 + fraction * (*b as f32 - *a as f32)).round() as u8;
+        }
+        color
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpectrogramConfig {
+    pub stft: StftConfig,
+    // Image rows, the image is one column per STFT frame wide
+    pub height: usize,
+    pub frequency_scale: FrequencyScale,
+    // Levels are in dBFS, a full scale sine is at 0 dB. Anything at or below floor_db gets
+    // the lowest color
+    pub floor_db: f32,
+    pub colormap: Colormap,
+}
+
+impl Default for SpectrogramConfig {
+    fn default() -> Self {
+        Self {
+            stft: StftConfig::default(),
+            height: 512,
+            frequency_scale: FrequencyScale::Linear,
+            floor_db: -100.0,
+            colormap: Colormap::Viridis,
+        }
+    }
+}
+
+// RGB image, rows from top to bottom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[u8; 3]>,
+}
+
+impl Image {
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        self.pixels[y * self.width + x]
+    }
+
+    // Binary PPM (P6), readable by most image viewers and trivial to parse in tests
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut bytes = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        bytes.extend(self.pixels.iter().flatten());
+        bytes
+    }
+
+    // 8 bit RGB PNG. The image data is stored without compression, which keeps kopek free
+    // of a deflate implementation at the cost of larger files
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(self.height * (1 + 3 * self.width));
+        for y in 0..self.height {
+            raw.push(0); // no filter
+            raw.extend(
+                self.pixels[y * self.width..(y + 1) * self.width]
+                    .iter()
+                    .flatten(),
+            );
+        }
+
+        // zlib stream made of stored deflate blocks
+        let mut zlib = vec![0x78, 0x01];
+        let mut blocks = raw.chunks(u16::MAX as usize).peekable();
+        if blocks.peek().is_none() {
+            zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+        }
+        while let Some(block) = blocks.next() {
+            zlib.push(blocks.peek().is_none() as u8);
+            zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+            zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+            zlib.extend_from_slice(block);
+        }
+        zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // 8 bits per channel, RGB, deflate, no filter method, not interlaced
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut bytes = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+        png_chunk(&mut bytes, b"IHDR", &header);
+        png_chunk(&mut bytes, b"IDAT", &zlib);
+        png_chunk(&mut bytes, b"IEND", &[]);
+        bytes
+    }
+
+    pub fn write_ppm<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&self.to_ppm())?;
+        writer.flush()
+    }
+
+    pub fn write_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&self.to_png())?;
+        writer.flush()
+    }
+}
+
+// Level of every bin in dBFS, one row of bins per frame
+pub fn levels_db<T: FftFloat>(stft: &Stft<T>) -> Vec<Vec<f32>> {
+    let window = Window::new(stft.config.window, stft.config.frame_size);
+    stft.frames
+        .iter()
+        .map(|frame| spectrum::dbfs_spectrum(frame, stft.config.frame_size, window.coherent_gain()))
+        .collect()
+}
+
+pub fn render<T: FftFloat>(stft: &Stft<T>, sample_rate: u32, config: &SpectrogramConfig) -> Image {
+    let levels = levels_db(stft);
+    let (width, height) = (levels.len(), config.height);
+    let bin_width = sample_rate as f32 / stft.config.frame_size as f32;
+    let nyquist = sample_rate as f32 / 2.0;
+    // Frequency at the top edge of row y, the top row ends at Nyquist
+    let row_edge = |y: f32| {
+        let t = 1.0 - y / height as f32;
+        match config.frequency_scale {
+            FrequencyScale::Linear => t * nyquist,
+            FrequencyScale::Log { min_frequency } => {
+                let min_frequency = min_frequency.clamp(f32::MIN_POSITIVE, nyquist);
+                min_frequency * (nyquist / min_frequency).powf(t)
+            }
+        }
+    };
+
+    let mut pixels = vec![config.colormap.color(0.0); width * height];
+    for y in 0..height {
+        let (low, high) = (
+            row_edge(y as f32 + 1.0) / bin_width,
+            row_edge(y as f32) / bin_width,
+        );
+        let (first, last) = (low.ceil() as usize, high.floor() as usize);
+        for (x, frame) in levels.iter().enumerate() {
+            let last = last.min(frame.len() - 1);
+            let level = match first <= last {
+                // Rows that span whole bins show the loudest of them
+                true => frame[first..=last]
+                    .iter()
+                    .fold(f32::NEG_INFINITY, |max, &l| max.max(l)),
+                // Rows narrower than a bin interpolate between the neighbouring bins
+                false => {
+                    let center = (low + high) / 2.0;
+                    let i = (center.floor() as usize).min(frame.len() - 1);
+                    let j = (i + 1).min(frame.len() - 1);
+                    let fraction = center - i as f32;
+                    frame[i] + fraction * (frame[j] - frame[i])
+                }
+            };
+            let t = 1.0 - level / config.floor_db.min(-f32::EPSILON);
+            pixels[y * width + x] = config.colormap.color(t);
+        }
+    }
+
+    Image {
+        width,
+        height,
+        pixels,
+    }
+}
+
+pub fn spectrogram<T: FftFloat>(
+    signal: &[T],
+    sample_rate: u32,
+    config: &SpectrogramConfig,
+) -> Image {
+    render(&stft::stft(signal, config.stft), sample_rate, config)
+}
+
+// Spectrogram of the mono downmix
+pub fn spectrogram_audio<S: DecoderSample>(
+    audio: &DecodedAudio<S>,
+    config: &SpectrogramConfig,
+) -> Image {
+    spectrogram(&audio.to_mono(), audio.sample_rate, config)
+}
+
+fn png_chunk(bytes: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = bytes.len();
+    bytes.extend_from_slice(kind);
+    bytes.extend_from_slice(data);
+    let crc = crc32(&bytes[start..]);
+    bytes.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65_521;
+        b = (b + a) % 65_521;
+    }
+    (b << 16) | a
+}
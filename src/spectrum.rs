@@ -0,0 +1,164 @@
+use crate::fft::FftFloat;
+use num::complex::Complex;
+
+// Distance between two bins in Hz
+pub fn bin_width(sample_rate: u32, fft_size: usize) -> f32 {
+    sample_rate as f32 / fft_size as f32
+}
+
+// Center frequency of bin in Hz
+pub fn bin_to_frequency(bin: usize, sample_rate: u32, fft_size: usize) -> f32 {
+    bin as f32 * bin_width(sample_rate, fft_size)
+}
+
+// Fractional bin of frequency, round it for the nearest bin
+pub fn frequency_to_bin(frequency: f32, sample_rate: u32, fft_size: usize) -> f32 {
+    frequency / bin_width(sample_rate, fft_size)
+}
+
+pub fn magnitude_spectrum<T: FftFloat>(spectrum: &[Complex<T>]) -> Vec<f32> {
+    spectrum
+        .iter()
+        .map(|c| c.norm().to_f32().unwrap_or_default())
+        .collect()
+}
+
+pub fn power_spectrum<T: FftFloat>(spectrum: &[Complex<T>]) -> Vec<f32> {
+    spectrum
+        .iter()
+        .map(|c| c.norm_sqr().to_f32().unwrap_or_default())
+        .collect()
+}
+
+// Levels relative to a full scale sine, which reads 0 dB at its peak bin. coherent_gain is
+// the one of the window the frame was multiplied with, 1 for no window. DC and Nyquist
+// have no mirrored half, a full scale DC offset reads +6 dB
+pub fn dbfs_spectrum<T: FftFloat>(
+    spectrum: &[Complex<T>],
+    fft_size: usize,
+    coherent_gain: f64,
+) -> Vec<f32> {
+    let reference = (fft_size as f64 * coherent_gain / 2.0) as f32;
+    magnitude_spectrum(spectrum)
+        .into_iter()
+        .map(|magnitude| amplitude_to_db(magnitude / reference))
+        .collect()
+}
+
+// Silence is clamped to the smallest positive f32, about -758 dB, instead of -inf
+pub fn amplitude_to_db(amplitude: f32) -> f32 {
+    20.0 * amplitude.max(f32::MIN_POSITIVE).log10()
+}
+
+pub fn db_to_amplitude(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Band {
+    pub low: f32,
+    // Geometric mean of low and high
+    pub center: f32,
+    pub high: f32,
+}
+
+impl Band {
+    pub fn new(low: f32, high: f32) -> Self {
+        Self {
+            low,
+            center: (low * high).sqrt(),
+            high,
+        }
+    }
+}
+
+// Bands of 1 / fraction octaves centered on 1 kHz, with the base ten octave ratio of
+// IEC 61260, so the centers land on the usual nominal 31.5, 63, 125, ... Hz. Every band
+// whose center is within min_frequency and max_frequency is included
+pub fn fractional_octave_bands(fraction: u32, min_frequency: f32, max_frequency: f32) -> Vec<Band> {
+    let fraction = fraction.max(1) as f32;
+    let octave = 10.0_f32.powf(0.3);
+    let half_band = octave.powf(0.5 / fraction);
+    // Band index relative to the 1 kHz band. The limits are widened by a twentieth of a band
+    // so a band counts when its nominal center is on a limit, e.g. 19.95 Hz for 20 Hz
+    let index = |frequency: f32| fraction * (frequency / 1000.0).log(octave);
+    let first = (index(min_frequency) - 0.05).ceil() as i32;
+    let last = (index(max_frequency) + 0.05).floor() as i32;
+    (first..=last)
+        .map(|i| {
+            let center = 1000.0 * octave.powf(i as f32 / fraction);
+            Band {
+                low: center / half_band,
+                center,
+                high: center * half_band,
+            }
+        })
+        .collect()
+}
+
+pub fn octave_bands(min_frequency: f32, max_frequency: f32) -> Vec<Band> {
+    fractional_octave_bands(1, min_frequency, max_frequency)
+}
+
+pub fn third_octave_bands(min_frequency: f32, max_frequency: f32) -> Vec<Band> {
+    fractional_octave_bands(3, min_frequency, max_frequency)
+}
+
+// count bands of equal width on a log axis, covering min_frequency to max_frequency
+pub fn log_bands(min_frequency: f32, max_frequency: f32, count: usize) -> Vec<Band> {
+    let edge =
+        |i: usize| min_frequency * (max_frequency / min_frequency).powf(i as f32 / count as f32);
+    (0..count)
+        .map(|i| Band::new(edge(i), edge(i + 1)))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation {
+    // Band energy when the values are powers
+    Sum,
+    Mean,
+    Max,
+}
+
+// Combines the bins of a one sided spectrum into bands. A bin belongs to the band its center
+// frequency falls in, from low inclusive to high exclusive. Bands narrower than a bin, common
+// for low bands of short FFTs, get the value interpolated at their center
+pub fn aggregate_bands(
+    values: &[f32],
+    bands: &[Band],
+    sample_rate: u32,
+    fft_size: usize,
+    aggregation: Aggregation,
+) -> Vec<f32> {
+    if values.is_empty() {
+        return vec![0.0; bands.len()];
+    }
+    let last_bin = values.len() - 1;
+    bands
+        .iter()
+        .map(|band| {
+            let first = frequency_to_bin(band.low, sample_rate, fft_size)
+                .max(0.0)
+                .ceil() as usize;
+            // First bin of the next band
+            let end = (frequency_to_bin(band.high, sample_rate, fft_size)
+                .max(0.0)
+                .ceil() as usize)
+                .min(values.len());
+            if first >= end {
+                let center = frequency_to_bin(band.center, sample_rate, fft_size)
+                    .clamp(0.0, last_bin as f32);
+                let i = (center.floor() as usize).min(last_bin);
+                let j = (i + 1).min(last_bin);
+                return values[i] + (center - i as f32) * (values[j] - values[i]);
+            }
+            let bins = &values[first..end];
+            match aggregation {
+                Aggregation::Sum => bins.iter().sum(),
+                Aggregation::Mean => bins.iter().sum::<f32>() / bins.len() as f32,
+                Aggregation::Max => bins.iter().fold(f32::NEG_INFINITY, |max, &v| max.max(v)),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,193 @@
+use crate::decoder::{DecodedAudio, DecoderSample};
+use crate::fft::{FftFloat, FftPlan, RfftPlan};
+use crate::window::{Window, WindowType};
+use num::complex::Complex;
+use num::Zero;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Padding {
+    // Frames start at the first sample, samples after the last whole frame are left out
+    None,
+    // Frame t is centered on sample t * hop_size, the signal is extended with zeros
+    Zero,
+    // Like Zero, but the signal is extended by mirroring it at its ends
+    Reflect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StftConfig {
+    pub frame_size: usize,
+    pub hop_size: usize,
+    pub window: WindowType,
+    pub padding: Padding,
+}
+
+impl Default for StftConfig {
+    // About 46 ms frames with 75% overlap at 44100 Hz
+    fn default() -> Self {
+        Self {
+            frame_size: 2048,
+            hop_size: 512,
+            window: WindowType::Hann,
+            padding: Padding::Zero,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Stft<T: FftFloat = f32> {
+    pub config: StftConfig,
+    // One spectrum per frame, each with the frame_size / 2 + 1 bins from 0 Hz to Nyquist
+    pub frames: Vec<Vec<Complex<T>>>,
+    // Length of the analysed signal, istft returns this many samples
+    pub signal_length: usize,
+}
+
+impl<T: FftFloat> Stft<T> {
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn bin_count(&self) -> usize {
+        self.config.frame_size / 2 + 1
+    }
+
+    // Sample where frame starts in the signal, negative when it starts in the padding
+    pub fn frame_start(&self, frame: usize) -> isize {
+        (frame * self.config.hop_size) as isize - padding_offset(&self.config) as isize
+    }
+}
+
+pub fn stft<T: FftFloat>(signal: &[T], config: StftConfig) -> Stft<T> {
+    let StftConfig {
+        frame_size,
+        hop_size,
+        ..
+    } = config;
+    assert!(
+        frame_size > 0 && hop_size > 0,
+        "frame_size and hop_size must be positive"
+    );
+
+    let offset = padding_offset(&config);
+    let frame_count = match config.padding {
+        Padding::None if signal.len() < frame_size => 0,
+        Padding::None => (signal.len() - frame_size) / hop_size + 1,
+        Padding::Zero | Padding::Reflect => signal.len() / hop_size + 1,
+    };
+    // Sample i of the padded signal
+    let sample = |i: usize| -> T {
+        let i = i as isize - offset as isize;
+        match config.padding {
+            Padding::Reflect => reflect(i, signal.len()).map_or(T::zero(), |i| signal[i]),
+            _ => usize::try_from(i)
+                .ok()
+                .and_then(|i| signal.get(i).copied())
+                .unwrap_or(T::zero()),
+        }
+    };
+
+    let window = Window::new(config.window, frame_size);
+    let plan = RfftPlan::new(frame_size);
+    let mut buffer = vec![T::zero(); frame_size];
+    let mut scratch = vec![Complex::zero(); plan.scratch_len()];
+    let frames = (0..frame_count)
+        .map(|frame| {
+            let start = frame * hop_size;
+            for (i, value) in buffer.iter_mut().enumerate() {
+                *value = sample(start + i);
+            }
+            window.apply(&mut buffer);
+            let mut spectrum = vec![Complex::zero(); plan.spectrum_len()];
+            plan.forward_with_scratch(&buffer, &mut spectrum, &mut scratch);
+            spectrum
+        })
+        .collect();
+
+    Stft {
+        config,
+        frames,
+        signal_length: signal.len(),
+    }
+}
+
+// Short-time Fourier transform of the mono downmix
+pub fn stft_audio<S: DecoderSample>(audio: &DecodedAudio<S>, config: StftConfig) -> Stft<f32> {
+    stft(&audio.to_mono(), config)
+}
+
+// Weighted overlap-add: every frame is transformed back, windowed again and added up, then
+// divided by the summed squared window. Gives back the analysed signal exactly wherever the
+// squared windows overlap to a nonzero sum, which is the case for any window that satisfies
+// COLA at the hop size, e.g. Hann with 50% or 75% overlap. With Padding::None the samples
+// that no frame covers come back as 0
+pub fn istft<T: FftFloat>(stft: &Stft<T>) -> Vec<T> {
+    let StftConfig {
+        frame_size,
+        hop_size,
+        ..
+    } = stft.config;
+    let offset = padding_offset(&stft.config);
+    let window = Window::new(stft.config.window, frame_size);
+    let plan = FftPlan::new(frame_size);
+    let mut buffer = vec![Complex::zero(); frame_size];
+    let mut scratch = vec![Complex::zero(); plan.scratch_len()];
+
+    let padded_length = (stft.signal_length + offset)
+        .max(stft.frames.len().saturating_sub(1) * hop_size + frame_size);
+    let mut output = vec![T::zero(); padded_length];
+    let mut weights = vec![T::zero(); padded_length];
+    for (frame, spectrum) in stft.frames.iter().enumerate() {
+        // Rebuild the mirrored half of the real spectrum
+        for (k, value) in buffer.iter_mut().enumerate() {
+            *value = match k < spectrum.len() {
+                true => spectrum[k],
+                false => spectrum[frame_size - k].conj(),
+            };
+        }
+        plan.inverse_with_scratch(&mut buffer, &mut scratch);
+
+        let start = frame * hop_size;
+        for (i, (value, &w)) in buffer.iter().zip(window.coefficients()).enumerate() {
+            let w = T::from(w).unwrap_or_else(T::one);
+            output[start + i] += value.re * w;
+            weights[start + i] += w * w;
+        }
+    }
+
+    // Weights that small only come from the tails of the window, where nothing can be
+    // reconstructed reliably
+    let threshold = T::from(1e-8).unwrap_or_else(T::zero);
+    output
+        .iter()
+        .zip(&weights)
+        .skip(offset)
+        .take(stft.signal_length)
+        .map(|(&value, &weight)| match weight > threshold {
+            true => value / weight,
+            false => T::zero(),
+        })
+        .collect()
+}
+
+// Number of padding samples before the signal
+fn padding_offset(config: &StftConfig) -> usize {
+    match config.padding {
+        Padding::None => 0,
+        Padding::Zero | Padding::Reflect => config.frame_size / 2,
+    }
+}
+
+// Index of sample i of a signal that is mirrored at its first and last sample, None for
+// an empty signal
+fn reflect(i: isize, length: usize) -> Option<usize> {
+    if length <= 1 {
+        return (length == 1).then_some(0);
+    }
+    let period = 2 * (length as isize - 1);
+    let i = i.rem_euclid(period);
+    Some(match i < length as isize {
+        true => i as usize,
+        false => (period - i) as usize,
+    })
+}
@@ -0,0 +1,221 @@
+use crate::decoder::{self, DecodeError};
+use audrey::dasp_sample::{Sample, I24};
+use audrey::hound::SampleFormat;
+use audrey::read::{BufFileReader, FormatError};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+// Reads a file block by block instead of loading all of it into memory.
+// Frames are stereo like decoder::decode, mono sources are duplicated to both channels.
+pub struct StreamingDecoder {
+    path: PathBuf,
+    file: BufFileReader,
+    block_size: usize,
+    sample_rate: u32,
+    channel_count: u32,
+    // Frame offset of the next frame that will be returned
+    position: u64,
+    // Decoded but not yet returned samples, interleaved
+    pending: VecDeque<i16>,
+    flac_buffer: Vec<i32>,
+}
+
+impl StreamingDecoder {
+    pub fn open<P>(path: P, block_size: usize) -> Result<Self, DecodeError>
+    where
+        P: AsRef<Path>,
+    {
+        assert!(block_size > 0, "block_size must be greater than 0");
+        let file = decoder::open(path.as_ref())?;
+        let description = file.description();
+        let channel_count = description.channel_count();
+        if !(1..=2).contains(&channel_count) {
+            return Err(DecodeError::UnsupportedChannelLayout(channel_count));
+        }
+
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            file,
+            block_size,
+            sample_rate: description.sample_rate(),
+            channel_count,
+            position: 0,
+            pending: VecDeque::new(),
+            flac_buffer: vec![],
+        })
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // Channel count of the source, blocks are always stereo
+    pub fn channel_count(&self) -> u32 {
+        self.channel_count
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    // None if the container doesn't store its length, e.g. Ogg Vorbis
+    #[allow(unreachable_patterns)] // depends on the enabled audrey features
+    pub fn total_frames(&self) -> Option<u64> {
+        match &self.file {
+            audrey::Reader::Wav(reader) => Some(reader.duration() as u64),
+            audrey::Reader::Flac(reader) => reader.streaminfo().samples,
+            _ => None,
+        }
+    }
+
+    // Clears block and fills it with up to block_size frames, returns the number of frames read.
+    // Only the last block of the file can be shorter than block_size, 0 means end of file.
+    pub fn read_block(&mut self, block: &mut Vec<[i16; 2]>) -> Result<usize, DecodeError> {
+        block.clear();
+        let channel_count = self.channel_count as usize;
+        while block.len() < self.block_size {
+            if self.pending.len() < channel_count && !self.fill_pending()? {
+                break;
+            }
+            while block.len() < self.block_size && self.pending.len() >= channel_count {
+                let left = self.pending.pop_front().unwrap_or_default();
+                let right = if channel_count == 2 {
+                    self.pending.pop_front().unwrap_or_default()
+                } else {
+                    left
+                };
+                block.push([left, right]);
+            }
+        }
+
+        self.position += block.len() as u64;
+        Ok(block.len())
+    }
+
+    pub fn next_block(&mut self) -> Result<Option<Vec<[i16; 2]>>, DecodeError> {
+        let mut block = Vec::with_capacity(self.block_size);
+        match self.read_block(&mut block)? {
+            0 => Ok(None),
+            _ => Ok(Some(block)),
+        }
+    }
+
+    // WAV files seek directly, compressed formats are decoded from the start up to frame
+    pub fn seek(&mut self, frame: u64) -> Result<(), DecodeError> {
+        let frame = match self.total_frames() {
+            Some(total_frames) => frame.min(total_frames),
+            None => frame,
+        };
+
+        if let audrey::Reader::Wav(reader) = &mut self.file {
+            reader.seek(frame as u32).map_err(DecodeError::Io)?;
+            self.pending.clear();
+            self.position = frame;
+            return Ok(());
+        }
+
+        if frame < self.position {
+            self.file = decoder::open(&self.path)?;
+            self.pending.clear();
+            self.position = 0;
+        }
+        let mut skipped = Vec::with_capacity(self.block_size);
+        while self.position < frame {
+            let remaining = (frame - self.position).min(self.block_size as u64) as usize;
+            let block_size = std::mem::replace(&mut self.block_size, remaining);
+            let result = self.read_block(&mut skipped);
+            self.block_size = block_size;
+            if result? == 0 {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Decodes the next chunk of the source into pending, returns false at the end of file
+    #[allow(unreachable_patterns)] // depends on the enabled audrey features
+    fn fill_pending(&mut self) -> Result<bool, DecodeError> {
+        let pending = &mut self.pending;
+        let sample_count = self.block_size * self.channel_count as usize;
+        match &mut self.file {
+            audrey::Reader::Wav(reader) => {
+                let spec = reader.spec();
+                let before = pending.len();
+                // Same dispatch as audrey, every depth is scaled to i16 by to_sample
+                match (spec.sample_format, spec.bits_per_sample) {
+                    (SampleFormat::Float, _) => {
+                        for sample in reader.samples::<f32>().take(sample_count) {
+                            pending.push_back(sample.map_err(FormatError::from)?.to_sample());
+                        }
+                    }
+                    (SampleFormat::Int, 8) => {
+                        for sample in reader.samples::<i8>().take(sample_count) {
+                            pending.push_back(sample.map_err(FormatError::from)?.to_sample());
+                        }
+                    }
+                    (SampleFormat::Int, 16) => {
+                        for sample in reader.samples::<i16>().take(sample_count) {
+                            pending.push_back(sample.map_err(FormatError::from)?);
+                        }
+                    }
+                    (SampleFormat::Int, 24) => {
+                        for sample in reader.samples::<i32>().take(sample_count) {
+                            let sample = I24::new_unchecked(sample.map_err(FormatError::from)?);
+                            pending.push_back(sample.to_sample());
+                        }
+                    }
+                    (SampleFormat::Int, 32) => {
+                        for sample in reader.samples::<i32>().take(sample_count) {
+                            pending.push_back(sample.map_err(FormatError::from)?.to_sample());
+                        }
+                    }
+                    _ => return Err(DecodeError::UnsupportedFormat),
+                }
+                Ok(pending.len() > before)
+            }
+            audrey::Reader::Flac(reader) => {
+                let bits = reader.streaminfo().bits_per_sample;
+                let buffer = std::mem::take(&mut self.flac_buffer);
+                let block = match reader.blocks().read_next_or_eof(buffer) {
+                    Ok(Some(block)) => block,
+                    Ok(None) => return Ok(false),
+                    Err(err) => return Err(FormatError::from(err).into()),
+                };
+                for i in 0..block.duration() {
+                    for channel in 0..block.channels() {
+                        let sample = block.sample(channel, i) << (32 - bits);
+                        pending.push_back(sample.to_sample());
+                    }
+                }
+                self.flac_buffer = block.into_buffer();
+                Ok(true)
+            }
+            audrey::Reader::OggVorbis(reader) => loop {
+                // Packets can be empty, keep reading until there are samples or the stream ends
+                match reader.read_dec_packet_itl() {
+                    Ok(Some(packet)) if packet.is_empty() => continue,
+                    Ok(Some(packet)) => {
+                        pending.extend(packet);
+                        return Ok(true);
+                    }
+                    Ok(None) => return Ok(false),
+                    Err(err) => return Err(FormatError::from(err).into()),
+                }
+            },
+            _ => Err(DecodeError::UnsupportedFormat),
+        }
+    }
+}
+
+impl Iterator for StreamingDecoder {
+    type Item = Result<Vec<[i16; 2]>, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_block().transpose()
+    }
+}
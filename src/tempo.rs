@@ -0,0 +1,191 @@
+use crate::decoder::{DecodedAudio, DecoderSample};
+use crate::fft;
+use crate::window::{Window, WindowType};
+use num::complex::Complex;
+use num::Zero;
+
+const MIN_BPM: f32 = 40.0;
+const MAX_BPM: f32 = 240.0;
+// Listeners prefer tempos around 120 BPM, this prior settles most half/double ambiguities
+const PRIOR_BPM: f32 = 120.0;
+const PRIOR_WIDTH_IN_OCTAVES: f32 = 1.0;
+// Period multiples summed by the comb filter
+const COMB_SIZE: usize = 4;
+const MAX_CANDIDATES: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoCandidate {
+    pub bpm: f32,
+    // Share of the total score of all candidates, between 0 and 1
+    pub confidence: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct OnsetEnvelope {
+    // One value per hop, high where new notes or hits start
+    pub values: Vec<f32>,
+    pub frame_size: usize,
+    pub hop_size: usize,
+    pub sample_rate: u32,
+}
+
+impl OnsetEnvelope {
+    // Envelope values per second
+    pub fn frame_rate(&self) -> f32 {
+        self.sample_rate as f32 / self.hop_size as f32
+    }
+}
+
+// Spectral flux of a mono signal: the summed increase of log magnitude over all bins
+// from one frame to the next. Power of two frame sizes are the fastest.
+pub fn onset_envelope(
+    samples: &[f32],
+    sample_rate: u32,
+    frame_size: usize,
+    hop_size: usize,
+) -> OnsetEnvelope {
+    let window = Window::new(WindowType::Hann, frame_size);
+    let plan = fft::RfftPlan::new(frame_size);
+    let mut scratch = vec![Complex::zero(); plan.scratch_len()];
+    let mut spectrum = vec![Complex::zero(); plan.spectrum_len()];
+
+    let mut previous: Vec<f64> = vec![0.0; frame_size / 2 + 1];
+    let mut flux = vec![];
+    let mut start = 0;
+    while start + frame_size <= samples.len() {
+        let mut input: Vec<f64> = samples[start..start + frame_size]
+            .iter()
+            .map(|&s| s as f64)
+            .collect();
+        window.apply(&mut input);
+        plan.forward_with_scratch(&input, &mut spectrum, &mut scratch);
+        // Log compression keeps loud sustained notes from hiding quiet onsets
+        let magnitudes: Vec<f64> = spectrum
+            .iter()
+            .map(|c| (1.0 + 100.0 * c.norm()).ln())
+            .collect();
+        let value: f64 = magnitudes
+            .iter()
+            .zip(&previous)
+            .map(|(m, p)| (m - p).max(0.0))
+            .sum();
+        flux.push(value as f32);
+        previous = magnitudes;
+        start += hop_size;
+    }
+    // The first frame is compared to silence
+    if let Some(first) = flux.first_mut() {
+        *first = 0.0;
+    }
+
+    // Subtract the local average, so only peaks that stand out are left
+    let reach = (sample_rate as usize / hop_size / 8).max(1);
+    let values = (0..flux.len())
+        .map(|i| {
+            let range = &flux[i.saturating_sub(reach)..(i + reach + 1).min(flux.len())];
+            let average = range.iter().sum::<f32>() / range.len() as f32;
+            (flux[i] - average).max(0.0)
+        })
+        .collect();
+
+    OnsetEnvelope {
+        values,
+        frame_size,
+        hop_size,
+        sample_rate,
+    }
+}
+
+// Frame and hop sizes of about 46 ms and 11.6 ms, 2048 and 512 samples at 44100 Hz
+pub fn default_onset_envelope(samples: &[f32], sample_rate: u32) -> OnsetEnvelope {
+    let frame_size = ((sample_rate as f32 * 0.046) as usize).next_power_of_two();
+    onset_envelope(samples, sample_rate, frame_size, frame_size / 4)
+}
+
+// Ranked from the most to the least likely tempo, empty if the signal is too short
+// to hold at least two beats at the slowest tempo
+pub fn estimate_tempo_from_envelope(envelope: &OnsetEnvelope) -> Vec<TempoCandidate> {
+    let frame_rate = envelope.frame_rate();
+    let min_lag = (60.0 * frame_rate / MAX_BPM).floor().max(1.0) as usize;
+    let max_lag = (60.0 * frame_rate / MIN_BPM).ceil() as usize;
+    let values = &envelope.values;
+    if values.len() <= 2 * max_lag {
+        return vec![];
+    }
+
+    // Long enough for every multiple searched around the longest lag when the signal allows
+    let autocorrelation = autocorrelation(values, COMB_SIZE * (max_lag + 2));
+    if autocorrelation[0] <= 0.0 {
+        return vec![];
+    }
+
+    // Comb filter, the true period also correlates at its multiples. The period is rarely a
+    // whole number of frames, so the peak is searched around each multiple
+    let scores: Vec<f32> = (0..=max_lag + 1)
+        .map(|lag| {
+            if lag < min_lag {
+                return 0.0;
+            }
+            // Multiples past the last computed lag are left out, clamping them to it would
+            // add the same value to every slow lag
+            let comb: f32 = (1..=COMB_SIZE)
+                .filter_map(|k| {
+                    let start = (k as f32 * (lag as f32 - 0.5)).round() as usize;
+                    let end = (k as f32 * (lag as f32 + 0.5)).round() as usize;
+                    let peak = autocorrelation
+                        .get(start..=end)?
+                        .iter()
+                        .fold(0.0_f32, |max, &a| max.max(a));
+                    Some(peak / k as f32)
+                })
+                .sum();
+            let bpm = 60.0 * frame_rate / lag as f32;
+            let octaves = (bpm / PRIOR_BPM).log2() / PRIOR_WIDTH_IN_OCTAVES;
+            comb.max(0.0) * (-0.5 * octaves * octaves).exp()
+        })
+        .collect();
+
+    let mut candidates: Vec<(f32, f32)> = (min_lag..=max_lag)
+        .filter(|&lag| scores[lag] > 0.0 && scores[lag] >= scores[lag - 1])
+        .filter(|&lag| scores[lag] > scores[lag + 1])
+        .map(|lag| {
+            // Parabolic interpolation for a fractional lag
+            let (a, b, c) = (scores[lag - 1], scores[lag], scores[lag + 1]);
+            let denominator = a - 2.0 * b + c;
+            let offset = match denominator.abs() > f32::EPSILON {
+                true => (0.5 * (a - c) / denominator).clamp(-0.5, 0.5),
+                false => 0.0,
+            };
+            (60.0 * frame_rate / (lag as f32 + offset), b)
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+    candidates.truncate(MAX_CANDIDATES);
+
+    let total: f32 = candidates.iter().map(|c| c.1).sum();
+    candidates
+        .into_iter()
+        .map(|(bpm, score)| TempoCandidate {
+            bpm,
+            confidence: score / total,
+        })
+        .collect()
+}
+
+pub fn estimate_tempo_from_samples(samples: &[f32], sample_rate: u32) -> Vec<TempoCandidate> {
+    estimate_tempo_from_envelope(&default_onset_envelope(samples, sample_rate))
+}
+
+pub fn estimate_tempo<S: DecoderSample>(audio: &DecodedAudio<S>) -> Vec<TempoCandidate> {
+    estimate_tempo_from_samples(&audio.to_mono(), audio.sample_rate)
+}
+
+// Unbiased autocorrelation for lags 0..max_lag, each lag is divided by its number of terms
+fn autocorrelation(values: &[f32], max_lag: usize) -> Vec<f32> {
+    (0..max_lag.min(values.len()))
+        .map(|lag| {
+            let sum: f32 = values.iter().zip(&values[lag..]).map(|(a, b)| a * b).sum();
+            sum / (values.len() - lag) as f32
+        })
+        .collect()
+}
@@ -16,6 +16,10 @@ impl TimeSignature {
         Self { time, metronome }
     }
 
+    pub fn from_metronome(time: (u8, u8), metronome: Metronome) -> Self {
+        Self { time, metronome }
+    }
+
     pub fn update(&mut self, elapsed_samples: u32) -> (bool, bool) {
         self.metronome.update(elapsed_samples);
 
@@ -0,0 +1,183 @@
+use crate::decoder::{DecodedAudio, DecoderSample};
+use crate::fft;
+use num::complex::Complex;
+use num::Zero;
+
+// Samples per cycle in every table, enough for 1023 harmonics
+pub const TABLE_SIZE: usize = 2048;
+// One table per octave, from all harmonics down to just the fundamental
+const LEVEL_COUNT: usize = TABLE_SIZE.trailing_zeros() as usize - 1;
+
+// Single cycle waveforms, called frames, each stored as band-limited copies per octave
+#[derive(Debug, Clone)]
+pub struct Wavetable {
+    // frames[frame][level] holds TABLE_SIZE samples with the first harmonics(level)
+    // harmonics
+    frames: Vec<Vec<Vec<f32>>>,
+}
+
+impl Wavetable {
+    // Cycles can be of any length, each one is resampled to TABLE_SIZE through its spectrum
+    pub fn new<C: AsRef<[f32]>>(cycles: &[C]) -> Self {
+        assert!(
+            cycles.iter().all(|cycle| !cycle.as_ref().is_empty()) && !cycles.is_empty(),
+            "a wavetable needs at least one non-empty cycle"
+        );
+        Self {
+            frames: cycles.iter().map(|cycle| mipmaps(cycle.as_ref())).collect(),
+        }
+    }
+
+    // Splits samples into consecutive cycles of cycle_length, the layout of most wavetable
+    // files, e.g. 2048 samples per frame. A partial cycle at the end is left out
+    pub fn from_samples(samples: &[f32], cycle_length: usize) -> Self {
+        assert!(cycle_length > 0, "cycle_length must be positive");
+        let cycles: Vec<&[f32]> = samples.chunks_exact(cycle_length).collect();
+        Self::new(&cycles)
+    }
+
+    // Cycles from the mono downmix of decoded audio, see from_samples
+    pub fn from_audio<S: DecoderSample>(audio: &DecodedAudio<S>, cycle_length: usize) -> Self {
+        Self::from_samples(&audio.to_mono(), cycle_length)
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    // Table of frame that is free of aliasing when played at frequency
+    pub fn table(&self, frame: usize, frequency: f32, sample_rate: f32) -> &[f32] {
+        &self.frames[frame][level(frequency, sample_rate)]
+    }
+}
+
+pub struct WavetableOscillator {
+    wavetable: Wavetable,
+    sample_rate: f32,
+    frequency: f32,
+    morph: f32,
+    // Position in the cycle in [0, 1), like Oscillator
+    phase: f64,
+}
+
+impl WavetableOscillator {
+    pub fn new(wavetable: Wavetable, sample_rate: f32) -> Self {
+        Self {
+            wavetable,
+            sample_rate,
+            frequency: 0.0,
+            morph: 0.0,
+            phase: 0.0,
+        }
+    }
+
+    pub fn wavetable(&self) -> &Wavetable {
+        &self.wavetable
+    }
+
+    pub fn set_wavetable(&mut self, wavetable: Wavetable) {
+        self.wavetable = wavetable;
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.frequency = frequency;
+    }
+
+    pub fn morph(&self) -> f32 {
+        self.morph
+    }
+
+    // Position between the frames, 0 plays the first and 1 the last, values in between
+    // crossfade the two nearest frames
+    pub fn set_morph(&mut self, morph: f32) {
+        self.morph = morph.clamp(0.0, 1.0);
+    }
+
+    pub fn phase(&self) -> f64 {
+        self.phase
+    }
+
+    pub fn set_phase(&mut self, phase: f64) {
+        self.phase = phase.rem_euclid(1.0);
+        if self.phase >= 1.0 {
+            self.phase = 0.0;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
+    pub fn run(&mut self) -> f32 {
+        if self.frequency < f32::EPSILON {
+            return 0.0;
+        }
+        let level = level(self.frequency, self.sample_rate);
+        let frames = &self.wavetable.frames;
+        let position = self.morph * (frames.len() - 1) as f32;
+        let first = (position as usize).min(frames.len() - 1);
+        let second = (first + 1).min(frames.len() - 1);
+        let fraction = position - first as f32;
+
+        let a = interpolate(&frames[first][level], self.phase);
+        let value = match first == second {
+            true => a,
+            false => a + fraction * (interpolate(&frames[second][level], self.phase) - a),
+        };
+
+        self.phase += self.frequency as f64 / self.sample_rate as f64;
+        self.phase -= self.phase.floor();
+
+        value
+    }
+}
+
+// Tables of one cycle for every level, from the cycle's spectrum with the harmonics above
+// each level's limit removed
+fn mipmaps(cycle: &[f32]) -> Vec<Vec<f32>> {
+    let spectrum = fft::rfft(cycle);
+    // Highest harmonic the cycle can hold, the Nyquist bin of an even length is left out as
+    // its phase is unknown
+    let max_harmonic = (cycle.len() - 1) / 2;
+    let scale = TABLE_SIZE as f32 / cycle.len() as f32;
+
+    (0..LEVEL_COUNT)
+        .map(|level| {
+            let mut table_spectrum = vec![Complex::zero(); TABLE_SIZE / 2 + 1];
+            for (k, value) in table_spectrum
+                .iter_mut()
+                .enumerate()
+                .take(harmonics(level).min(max_harmonic) + 1)
+            {
+                *value = spectrum[k] * scale;
+            }
+            fft::irfft(&table_spectrum, TABLE_SIZE)
+        })
+        .collect()
+}
+
+// 1023 harmonics for level 0, halved every level down to 1
+fn harmonics(level: usize) -> usize {
+    (TABLE_SIZE / 2 - 1) >> level
+}
+
+// Lowest level whose harmonics all stay below Nyquist at frequency
+fn level(frequency: f32, sample_rate: f32) -> usize {
+    let limit = sample_rate / 2.0 / frequency.max(f32::EPSILON);
+    (0..LEVEL_COUNT)
+        .find(|&level| harmonics(level) as f32 <= limit)
+        .unwrap_or(LEVEL_COUNT - 1)
+}
+
+// Linear interpolation at phase in [0, 1), wrapping around the end of the cycle
+fn interpolate(table: &[f32], phase: f64) -> f32 {
+    let position = phase * table.len() as f64;
+    let i = position as usize % table.len();
+    let j = (i + 1) % table.len();
+    let fraction = (position - position.floor()) as f32;
+    table[i] + fraction * (table[j] - table[i])
+}
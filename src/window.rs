@@ -0,0 +1,155 @@
+use crate::fft::FftFloat;
+use num::complex::Complex;
+use std::f64::consts::PI;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowType {
+    // No window, same as leaving the buffer as it is
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+    // 4 term, sidelobes below -92 dB
+    BlackmanHarris,
+    // Nearly flat main lobe, for reading amplitudes of sinusoids off the spectrum
+    FlatTop,
+    // Larger beta trades a wider main lobe for lower sidelobes, 0 is rectangular
+    Kaiser { beta: f64 },
+    // Cosine tapered edges over a fraction alpha of the window, 0 is rectangular, 1 is Hann
+    Tukey { alpha: f64 },
+}
+
+impl WindowType {
+    // Value at index i of a periodic window of the given size. Periodic windows repeat
+    // seamlessly, which is what the FFT and overlap-add expect
+    pub fn value(&self, i: usize, size: usize) -> f64 {
+        let x = i as f64 / size as f64;
+        match *self {
+            WindowType::Rectangular => 1.0,
+            WindowType::Hann => cosine_sum(&[0.5, 0.5], x),
+            WindowType::Hamming => cosine_sum(&[0.54, 0.46], x),
+            WindowType::Blackman => cosine_sum(&[0.42, 0.5, 0.08], x),
+            WindowType::BlackmanHarris => cosine_sum(&[0.35875, 0.48829, 0.14128, 0.01168], x),
+            WindowType::FlatTop => cosine_sum(
+                &[
+                    0.21557895,
+                    0.41663158,
+                    0.277263158,
+                    0.083578947,
+                    0.006947368,
+                ],
+                x,
+            ),
+            WindowType::Kaiser { beta } => {
+                let t = 2.0 * x - 1.0;
+                bessel_i0(beta * (1.0 - t * t).max(0.0).sqrt()) / bessel_i0(beta)
+            }
+            WindowType::Tukey { alpha } => {
+                let alpha = alpha.clamp(0.0, 1.0);
+                // Distance from the nearest edge, the taper covers alpha / 2 on each side
+                let edge = x.min(1.0 - x);
+                match edge < alpha / 2.0 {
+                    true => 0.5 - 0.5 * (2.0 * PI * edge / alpha).cos(),
+                    false => 1.0,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Window {
+    window_type: WindowType,
+    coefficients: Vec<f64>,
+}
+
+impl Window {
+    pub fn new(window_type: WindowType, size: usize) -> Self {
+        Self {
+            window_type,
+            coefficients: (0..size).map(|i| window_type.value(i, size)).collect(),
+        }
+    }
+
+    pub fn window_type(&self) -> WindowType {
+        self.window_type
+    }
+
+    pub fn size(&self) -> usize {
+        self.coefficients.len()
+    }
+
+    pub fn coefficients(&self) -> &[f64] {
+        &self.coefficients
+    }
+
+    // Mean of the window, the factor a sinusoid's peak in the spectrum is scaled by.
+    // Divide magnitudes by size * coherent_gain to read amplitudes
+    pub fn coherent_gain(&self) -> f64 {
+        self.coefficients.iter().sum::<f64>() / self.size() as f64
+    }
+
+    // Equivalent noise bandwidth in bins, how much broadband noise each bin collects
+    // compared to a rectangular window. Multiply by the bin width for Hz
+    pub fn enbw(&self) -> f64 {
+        let sum: f64 = self.coefficients.iter().sum();
+        let sum_of_squares: f64 = self.coefficients.iter().map(|w| w * w).sum();
+        self.size() as f64 * sum_of_squares / (sum * sum)
+    }
+
+    // Multiplies buffer by the window, buffer has to be exactly size long
+    pub fn apply<T: FftFloat>(&self, buffer: &mut [T]) {
+        assert_eq!(
+            self.size(),
+            buffer.len(),
+            "buffer length must match the window"
+        );
+        for (x, &w) in buffer.iter_mut().zip(&self.coefficients) {
+            *x *= T::from(w).unwrap_or_else(T::one);
+        }
+    }
+
+    pub fn apply_complex<T: FftFloat>(&self, buffer: &mut [Complex<T>]) {
+        assert_eq!(
+            self.size(),
+            buffer.len(),
+            "buffer length must match the window"
+        );
+        for (x, &w) in buffer.iter_mut().zip(&self.coefficients) {
+            *x = x.scale(T::from(w).unwrap_or_else(T::one));
+        }
+    }
+}
+
+// Applies a window of the buffer's length, make a Window once instead when windowing
+// many buffers of the same size
+pub fn apply<T: FftFloat>(window_type: WindowType, buffer: &mut [T]) {
+    Window::new(window_type, buffer.len()).apply(buffer);
+}
+
+// Sum of a0 - a1 cos(2πx) + a2 cos(4πx) - ..., the form of the Hann and Blackman families
+fn cosine_sum(coefficients: &[f64], x: f64) -> f64 {
+    coefficients
+        .iter()
+        .enumerate()
+        .map(|(k, a)| {
+            let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+            sign * a * (2.0 * PI * k as f64 * x).cos()
+        })
+        .sum()
+}
+
+// Zeroth order modified Bessel function of the first kind, from its power series
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half_x = x / 2.0;
+    for k in 1..100 {
+        term *= (half_x / k as f64) * (half_x / k as f64);
+        sum += term;
+        if term < sum * 1e-16 {
+            break;
+        }
+    }
+    sum
+}